@@ -0,0 +1,22 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use sniffer::Sniffer;
+
+fn saturation(c: &mut Criterion) {
+    for example in ["nspk", "nspk-ab"] {
+        let path = format!("examples/{example}.pif");
+        c.bench_function(&format!("saturate {example}"), |b| {
+            b.iter(|| Sniffer::new(&path).unwrap().saturate())
+        });
+        #[cfg(feature = "parallel")]
+        c.bench_function(&format!("saturate {example} (parallel)"), |b| {
+            b.iter(|| {
+                let mut sniffer = Sniffer::new(&path).unwrap();
+                sniffer.set_parallel(true);
+                sniffer.saturate()
+            })
+        });
+    }
+}
+
+criterion_group!(benches, saturation);
+criterion_main!(benches);
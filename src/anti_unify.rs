@@ -0,0 +1,199 @@
+//! Anti-unification module
+//! Computes least general generalisations of terms, atoms and rules, which helps spotting
+//! the families of clauses (`att(pair(a, pair(a, ...)))`) that make saturation diverge
+use crate::ast::{Atom, InnerAtom, InnerRule, InnerTerm, Rule, Term};
+use crate::identifiers::{Identifier, VariableAllocator};
+use rustc_hash::FxHashMap;
+
+/// Remembers which variable generalises a given pair of terms, so that
+/// `lgg(f(a, a), f(b, b))` gives `f(X, X)` and not `f(X, Y)`
+pub type Generalisations = FxHashMap<(InnerTerm, InnerTerm), Identifier>;
+
+impl InnerTerm {
+    /// Returns the least general term of which both `self` and `other` are instances
+    pub fn generalise(
+        &self,
+        other: &InnerTerm,
+        variables: &VariableAllocator,
+        pairs: &mut Generalisations,
+    ) -> InnerTerm {
+        if self == other {
+            return self.clone();
+        }
+
+        match (self, other) {
+            (
+                Term::Function {
+                    symbol: f,
+                    parameters: f_params,
+                },
+                Term::Function {
+                    symbol: g,
+                    parameters: g_params,
+                },
+            ) if f == g && f_params.len() == g_params.len() => Term::Function {
+                symbol: *f,
+                parameters: f_params
+                    .iter()
+                    .zip(g_params)
+                    .map(|(t, u)| t.generalise(u, variables, pairs))
+                    .collect(),
+            },
+            _ => {
+                let symbol = *pairs
+                    .entry((self.clone(), other.clone()))
+                    .or_insert_with(|| variables.fresh());
+                Term::Variable { symbol }
+            }
+        }
+    }
+}
+
+impl InnerAtom {
+    /// Returns the least general atom of which both `self` and `other` are instances,
+    /// or `None` if they do not share the same symbol and arity
+    pub fn generalise(
+        &self,
+        other: &InnerAtom,
+        variables: &VariableAllocator,
+        pairs: &mut Generalisations,
+    ) -> Option<InnerAtom> {
+        if self.symbol != other.symbol || self.parameters.len() != other.parameters.len() {
+            return None;
+        }
+
+        Some(Atom {
+            symbol: self.symbol,
+            parameters: self
+                .parameters
+                .iter()
+                .zip(&other.parameters)
+                .map(|(t, u)| t.generalise(u, variables, pairs))
+                .collect(),
+        })
+    }
+}
+
+impl InnerRule {
+    /// Returns the least general rule of which both `self` and `other` are instances.
+    /// Both rules must have the same conclusion symbol and premises symbols, in the same order.
+    /// Their constraints are dropped, which only makes the generalisation more general
+    pub fn generalise(
+        &self,
+        other: &InnerRule,
+        variables: &VariableAllocator,
+    ) -> Option<InnerRule> {
+        if self.premises.len() != other.premises.len() {
+            return None;
+        }
+
+        // The same pairs mapping is used throughout the rule, to keep track of variables
+        // shared between premises and conclusion
+        let mut pairs = Generalisations::default();
+        let conclusion = self
+            .conclusion
+            .generalise(&other.conclusion, variables, &mut pairs)?;
+        let premises = self
+            .premises
+            .iter()
+            .zip(&other.premises)
+            .map(|(p, q)| p.generalise(q, variables, &mut pairs))
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Rule {
+            premises,
+            conclusion,
+            constraints: vec![],
+        })
+    }
+}
+
+/// Generalises a whole family of rules, returning `None` if the family is empty
+/// or if two of its rules are not compatible
+pub fn generalise_all<'a>(
+    mut rules: impl Iterator<Item = &'a InnerRule>,
+    variables: &VariableAllocator,
+) -> Option<InnerRule> {
+    let first = rules.next()?.clone();
+    rules.try_fold(first, |generalisation, r| {
+        generalisation.generalise(r, variables)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn constant(i: usize) -> InnerTerm {
+        Term::Function {
+            symbol: Identifier::Function(i),
+            parameters: vec![],
+        }
+    }
+
+    fn pair(x: InnerTerm, y: InnerTerm) -> InnerTerm {
+        Term::Function {
+            symbol: Identifier::Function(10),
+            parameters: vec![x, y],
+        }
+    }
+
+    #[test]
+    fn generalise_identical_test() {
+        let t = pair(constant(0), constant(1));
+        let g = t.generalise(&t, &VariableAllocator::default(), &mut Default::default());
+        assert_eq!(g, t);
+    }
+
+    #[test]
+    fn generalise_shared_pair_test() {
+        let variables = VariableAllocator::default();
+        let t = pair(constant(0), constant(0));
+        let u = pair(constant(1), constant(1));
+
+        let g = t.generalise(&u, &variables, &mut Default::default());
+        let Term::Function { parameters, .. } = g else {
+            panic!("Expected function")
+        };
+        assert!(parameters[0].is_variable());
+        assert_eq!(parameters[0], parameters[1]);
+    }
+
+    #[test]
+    fn generalise_nested_test() {
+        let variables = VariableAllocator::default();
+        let t = pair(constant(0), pair(constant(0), constant(1)));
+        let u = pair(
+            constant(0),
+            pair(constant(0), pair(constant(0), constant(1))),
+        );
+
+        let g = t.generalise(&u, &variables, &mut Default::default());
+        let Term::Function { parameters, .. } = g else {
+            panic!("Expected function")
+        };
+        assert_eq!(parameters[0], constant(0));
+        let Term::Function { parameters, .. } = &parameters[1] else {
+            panic!("Expected function")
+        };
+        assert_eq!(parameters[0], constant(0));
+        assert!(parameters[1].is_variable());
+    }
+
+    #[test]
+    fn generalise_different_atoms_test() {
+        let a = Atom {
+            symbol: Identifier::Function(0),
+            parameters: vec![constant(1)],
+        };
+        let b = Atom {
+            symbol: Identifier::Function(2),
+            parameters: vec![constant(1)],
+        };
+
+        let variables = VariableAllocator::default();
+        assert!(a
+            .generalise(&b, &variables, &mut Default::default())
+            .is_none());
+    }
+}
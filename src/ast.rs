@@ -2,14 +2,39 @@
 //! High level representation of the constructs used in `.pif` files
 use crate::resolution::Selection;
 use crate::Identifier;
+use itertools::Itertools;
 use ptree::{Color, Style};
 use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
 use std::hash::Hash;
 
+/// Position of a construct in its source, as a range of bytes
+#[derive(Debug, Clone, Copy, Default, Hash, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+impl Span {
+    /// Returns the line and column (both starting from 1) at which the span starts
+    pub fn line_col(&self, source: &str) -> (usize, usize) {
+        let before = &source[..self.start.min(source.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.chars().rev().take_while(|c| *c != '\n').count() + 1;
+        (line, column)
+    }
+}
+
+/// Suffix of the symbols of session-indexed names: `na[S]` is the symbol `na[]` applied to `S`
+pub const NAME_SUFFIX: &str = "[]";
+
 pub type InnerTerm = Term<Identifier>;
 /// Represents parsed terms
-#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+///
+/// Serialized as `{"kind": "function", "symbol": ..., "parameters": [...]}`
+/// or `{"kind": "variable", "symbol": ...}`
+#[derive(Debug, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
 pub enum Term<T> {
     Function { symbol: T, parameters: Vec<Term<T>> },
     Variable { symbol: T },
@@ -34,19 +59,57 @@ impl<T: Clone + Hash + Eq + PartialEq + Debug> Term<T> {
             }
             Term::Function { symbol, parameters } => Term::Function {
                 symbol: symbol.clone(),
-                parameters: parameters
-                    .iter()
-                    .cloned()
-                    .map(|t| t.apply(bindings))
-                    .collect(),
+                parameters: parameters.iter().map(|t| t.apply(bindings)).collect(),
             },
         }
     }
 
+    /// Applies a valuation of the variables until reaching its fixpoint, for bindings
+    /// that refer to one another
+    pub fn apply_fully(&self, bindings: &FxHashMap<Term<T>, Term<T>>) -> Term<T> {
+        let mut term = self.clone();
+        for _ in 0..=bindings.len() {
+            let applied = term.apply(bindings);
+            if applied == term {
+                break;
+            }
+            term = applied;
+        }
+        term
+    }
+
     pub fn is_variable(&self) -> bool {
         matches!(self, Term::Variable { .. })
     }
 
+    /// Checks if the term does not contain any variable
+    pub fn is_ground(&self) -> bool {
+        match self {
+            Term::Function { parameters, .. } => parameters.iter().all(|t| t.is_ground()),
+            Term::Variable { .. } => false,
+        }
+    }
+
+    /// Returns the depth of the term, constants and variables being of depth 1
+    pub fn depth(&self) -> usize {
+        match self {
+            Term::Function { parameters, .. } => {
+                1 + parameters.iter().map(|t| t.depth()).max().unwrap_or(0)
+            }
+            Term::Variable { .. } => 1,
+        }
+    }
+
+    /// Returns the number of symbols and variables of the term
+    pub fn size(&self) -> usize {
+        match self {
+            Term::Function { parameters, .. } => {
+                1 + parameters.iter().map(|t| t.size()).sum::<usize>()
+            }
+            Term::Variable { .. } => 1,
+        }
+    }
+
     pub fn contains_variable(&self, variable: &Term<T>) -> bool {
         let Term::Variable {symbol} = variable else { panic!("Expected variable")};
         match self {
@@ -56,6 +119,14 @@ impl<T: Clone + Hash + Eq + PartialEq + Debug> Term<T> {
             Term::Variable { symbol: v } => v == symbol,
         }
     }
+
+    /// Lists the function symbols of the term along with the number of arguments they are given
+    pub fn symbols<'a>(&'a self, symbols: &mut Vec<(&'a T, usize)>) {
+        if let Term::Function { symbol, parameters } = self {
+            symbols.push((symbol, parameters.len()));
+            parameters.iter().for_each(|t| t.symbols(symbols))
+        }
+    }
 }
 /// Allows transformation of Atoms to Terms seamlessly
 impl<T> From<Atom<T>> for Term<T> {
@@ -66,9 +137,13 @@ impl<T> From<Atom<T>> for Term<T> {
         }
     }
 }
-impl<T: std::fmt::Display> std::fmt::Display for Term<T> {
+impl std::fmt::Display for Term<String> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            Term::Function { symbol, parameters } if self.is_name() => {
+                let name = symbol.strip_suffix(NAME_SUFFIX).unwrap();
+                write!(f, "{name}[{}]", format_vec(parameters, ", "))
+            }
             Term::Function { symbol, parameters } if parameters.is_empty() => {
                 write!(f, "{symbol}")
             }
@@ -87,7 +162,9 @@ pub type InnerAtom = Atom<Identifier>;
 /// Represents parsed atoms, which are named lists of terms
 /// Those are equivalent to Term::Function but necessary to avoid having variables
 /// as top level objects
-#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+///
+/// Serialized as `{"symbol": ..., "parameters": [...]}`
+#[derive(Debug, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Atom<T> {
     pub symbol: T,
     pub parameters: Vec<Term<T>>,
@@ -95,17 +172,27 @@ pub struct Atom<T> {
 impl<T: Clone + Hash + Eq + PartialEq + Debug> Atom<T> {
     /// Applies a valuation of the variables to this rule
     pub fn apply(&self, bindings: &FxHashMap<Term<T>, Term<T>>) -> Atom<T> {
+        Atom {
+            symbol: self.symbol.clone(),
+            parameters: self.parameters.iter().map(|t| t.apply(bindings)).collect(),
+        }
+    }
+
+    pub fn apply_fully(&self, bindings: &FxHashMap<Term<T>, Term<T>>) -> Atom<T> {
         Atom {
             symbol: self.symbol.clone(),
             parameters: self
                 .parameters
                 .iter()
-                .cloned()
-                .map(|t| t.apply(bindings))
+                .map(|t| t.apply_fully(bindings))
                 .collect(),
         }
     }
 
+    pub fn is_ground(&self) -> bool {
+        self.parameters.iter().all(|t| t.is_ground())
+    }
+
     pub fn contains_variable(&self, variable: &Term<T>) -> bool {
         self.parameters
             .iter()
@@ -121,6 +208,12 @@ impl<T: Clone + Hash + Eq + PartialEq + Debug> Atom<T> {
     pub fn is_smth_of_variable(&self) -> bool {
         self.parameters.len() == 1 && self.parameters[0].is_variable()
     }
+
+    /// Lists the predicate and function symbols of the atom along with the number of arguments they are given
+    pub fn symbols<'a>(&'a self, symbols: &mut Vec<(&'a T, usize)>) {
+        symbols.push((&self.symbol, self.parameters.len()));
+        self.parameters.iter().for_each(|t| t.symbols(symbols))
+    }
 }
 impl<T> TryFrom<Term<T>> for Atom<T> {
     type Error = ();
@@ -132,7 +225,7 @@ impl<T> TryFrom<Term<T>> for Atom<T> {
         }
     }
 }
-impl<T: std::fmt::Display> std::fmt::Display for Atom<T> {
+impl std::fmt::Display for Atom<String> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let Atom { symbol, parameters } = self;
         if parameters.is_empty() {
@@ -144,34 +237,119 @@ impl<T: std::fmt::Display> std::fmt::Display for Atom<T> {
     }
 }
 
+/// Represents the declarations of `.pif` files, which give sorts to function symbols
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Declaration {
+    /// `type key.`
+    Type(String),
+    /// `fun senc(bitstring, key): bitstring.`, or `fun k: key.` for constants
+    Function {
+        symbol: String,
+        parameters: Vec<String>,
+        result: String,
+    },
+}
+
+pub type InnerDisequality = Disequality<Identifier>;
+/// Represents a constraint `left <> right` on the instances of a rule, written among its
+/// premises (see the `constraints` module)
+///
+/// Serialized as `{"left": ..., "right": ...}`
+#[derive(Debug, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Disequality<T> {
+    pub left: Term<T>,
+    pub right: Term<T>,
+}
+impl<T: Clone + Hash + Eq + PartialEq + Debug> Disequality<T> {
+    pub fn apply(&self, bindings: &FxHashMap<Term<T>, Term<T>>) -> Disequality<T> {
+        Disequality {
+            left: self.left.apply(bindings),
+            right: self.right.apply(bindings),
+        }
+    }
+
+    pub fn apply_fully(&self, bindings: &FxHashMap<Term<T>, Term<T>>) -> Disequality<T> {
+        Disequality {
+            left: self.left.apply_fully(bindings),
+            right: self.right.apply_fully(bindings),
+        }
+    }
+
+    pub fn contains_variable(&self, variable: &Term<T>) -> bool {
+        self.left.contains_variable(variable) || self.right.contains_variable(variable)
+    }
+}
+impl std::fmt::Display for Disequality<String> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} <> {}", self.left, self.right)
+    }
+}
+
 pub type InnerRule = Rule<Identifier>;
-/// Represents parsed rules as a list of premisses and the concluded atom
-#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+/// Represents parsed rules as a list of premisses and the concluded atom, along with the
+/// disequality constraints written among the premises
+///
+/// Serialized as `{"premises": [...], "conclusion": ...}`, with `"constraints": [...]` if there are any
+#[derive(Debug, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Rule<T> {
     pub premises: Vec<Atom<T>>,
     pub conclusion: Atom<T>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub constraints: Vec<Disequality<T>>,
 }
 impl<T: Clone + Hash + Eq + PartialEq + Debug> Rule<T> {
     /// Applies a valuation of the variables to this rule
     pub fn apply(&self, bindings: &FxHashMap<Term<T>, Term<T>>) -> Rule<T> {
         Rule {
             conclusion: self.conclusion.apply(bindings),
+            premises: self.premises.iter().map(|a| a.apply(bindings)).collect(),
+            constraints: self.constraints.iter().map(|c| c.apply(bindings)).collect(),
+        }
+    }
+
+    pub fn apply_fully(&self, bindings: &FxHashMap<Term<T>, Term<T>>) -> Rule<T> {
+        Rule {
+            conclusion: self.conclusion.apply_fully(bindings),
             premises: self
                 .premises
                 .iter()
-                .cloned()
-                .map(|a| a.apply(bindings))
+                .map(|a| a.apply_fully(bindings))
+                .collect(),
+            constraints: self
+                .constraints
+                .iter()
+                .map(|c| c.apply_fully(bindings))
                 .collect(),
         }
     }
+
+    /// Lists the predicate and function symbols of the rule along with the number of arguments they are
+    /// given, in order of appearance
+    pub fn symbols(&self) -> Vec<(&T, usize)> {
+        let mut symbols = vec![];
+        self.premises
+            .iter()
+            .chain(std::iter::once(&self.conclusion))
+            .for_each(|a| a.symbols(&mut symbols));
+        self.constraints.iter().for_each(|c| {
+            c.left.symbols(&mut symbols);
+            c.right.symbols(&mut symbols)
+        });
+        symbols
+    }
 }
 
-impl<T: std::fmt::Display> Rule<T> {
+impl Rule<String> {
     pub fn selection_empathized_string(&self, selection: Selection<String>) -> String {
         let Rule {
             premises,
             conclusion,
+            constraints,
         } = self;
+        let constraints_pp = constraints
+            .iter()
+            .map(|c| format!(" /\\ {c}"))
+            .collect::<String>();
 
         let empathize_style = Style {
             foreground: Some(Color::Red),
@@ -181,39 +359,124 @@ impl<T: std::fmt::Display> Rule<T> {
 
         match selection {
             Selection::Conclusion(_) => {
-                if premises.is_empty() {
+                if premises.is_empty() && constraints.is_empty() {
                     format!("{}", empathize_style.paint(conclusion))
                 } else {
                     let premises_pp = format_vec(premises, " /\\ ");
-                    format!("{premises_pp} => {}", empathize_style.paint(conclusion))
+                    format!(
+                        "{premises_pp}{constraints_pp} => {}",
+                        empathize_style.paint(conclusion)
+                    )
                 }
             }
             Selection::Premise(_, i) => {
-                if premises.is_empty() {
+                if premises.is_empty() && constraints.is_empty() {
                     format!("{conclusion}")
                 } else {
                     let premises_pp = format_vec_empathize(premises, " /\\ ", i, empathize_style);
-                    format!("{premises_pp} => {conclusion}")
+                    format!("{premises_pp}{constraints_pp} => {conclusion}")
                 }
             }
         }
     }
 }
-impl<T: std::fmt::Display> std::fmt::Display for Rule<T> {
+impl std::fmt::Display for Rule<String> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let Rule {
             premises,
             conclusion,
+            constraints,
         } = self;
-        if premises.is_empty() {
+        if premises.is_empty() && constraints.is_empty() {
             write!(f, "{conclusion}")
         } else {
-            let premises_pp = format_vec(premises, " /\\ ");
+            let premises_pp = premises
+                .iter()
+                .map(|p| p.to_string())
+                .chain(constraints.iter().map(|c| c.to_string()))
+                .join(" /\\ ");
             write!(f, "{premises_pp} => {conclusion}")
         }
     }
 }
 
+impl Term<String> {
+    /// Checks if the term is a session-indexed name
+    pub fn is_name(&self) -> bool {
+        matches!(self, Term::Function { symbol, .. } if symbol.ends_with(NAME_SUFFIX))
+    }
+
+    /// Renders the term in LaTeX math mode, functions in sans-serif and variables in italics
+    pub fn to_latex(&self) -> String {
+        match self {
+            Term::Function { symbol, parameters } if self.is_name() => format!(
+                "\\mathsf{{{}}}[{}]",
+                escape_latex(symbol.strip_suffix(NAME_SUFFIX).unwrap()),
+                parameters.iter().map(|t| t.to_latex()).join(", ")
+            ),
+            Term::Function { symbol, parameters } if parameters.is_empty() => {
+                format!("\\mathsf{{{}}}", escape_latex(symbol))
+            }
+            Term::Function { symbol, parameters } => format!(
+                "\\mathsf{{{}}}({})",
+                escape_latex(symbol),
+                parameters.iter().map(|t| t.to_latex()).join(", ")
+            ),
+            Term::Variable { symbol } => format!("\\mathit{{{}}}", escape_latex(symbol)),
+        }
+    }
+}
+impl Atom<String> {
+    /// Renders the atom in LaTeX math mode
+    pub fn to_latex(&self) -> String {
+        Term::from(self.clone()).to_latex()
+    }
+}
+impl Rule<String> {
+    /// Renders the rule in LaTeX math mode, emphasizing the selected atom if one is given
+    pub fn selection_empathized_latex(&self, selection: Option<&Selection<String>>) -> String {
+        let empathize = |latex: String| format!("\\textcolor{{red}}{{\\boldsymbol{{{latex}}}}}");
+
+        let conclusion = match selection {
+            Some(Selection::Conclusion(_)) => empathize(self.conclusion.to_latex()),
+            _ => self.conclusion.to_latex(),
+        };
+        if self.premises.is_empty() && self.constraints.is_empty() {
+            return conclusion;
+        }
+
+        let premises = self
+            .premises
+            .iter()
+            .enumerate()
+            .map(|(i, p)| match selection {
+                Some(Selection::Premise(_, j)) if i == *j => empathize(p.to_latex()),
+                _ => p.to_latex(),
+            })
+            .chain(
+                self.constraints
+                    .iter()
+                    .map(|c| format!("{} \\neq {}", c.left.to_latex(), c.right.to_latex())),
+            )
+            .join(" \\wedge ");
+        format!("{premises} \\Rightarrow {conclusion}")
+    }
+}
+
+/// Escapes the characters LaTeX treats specially inside of a symbol name
+fn escape_latex(symbol: &str) -> String {
+    symbol
+        .chars()
+        .map(|c| match c {
+            '_' | '&' | '%' | '$' | '#' | '{' | '}' => format!("\\{c}"),
+            '\\' => String::from("\\backslash{}"),
+            '^' => String::from("\\hat{}"),
+            '~' => String::from("\\sim{}"),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
 /// Helper function to pretty print vectors
 fn format_vec<T: std::fmt::Display>(v: &[T], sep: &str) -> String {
     v.iter()
@@ -0,0 +1,429 @@
+//! Attack module
+//! Linearises a derivation tree into the sequence of messages the attacker learns, sends
+//! and receives, from the input clauses at its leaves instantiated with the unifiers
+//! of the resolution steps
+use crate::ast::{Atom, InnerAtom, InnerRule, InnerTerm, Rule, Term};
+use crate::correspondence::EVENT_PREDICATE;
+use crate::derivation_tree::DerivationTree;
+use crate::identifiers::{Identifier, IdentifierServer};
+use crate::phase::PHASE_PREDICATE;
+use crate::resolution::Selection;
+use itertools::Itertools;
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+/// Symbol of the predicate representing the attacker's knowledge
+pub const ATTACKER_PREDICATE: &str = "att";
+
+/// Identifiers of the predicates representing the attacker's knowledge, `att(M)` and
+/// `att_phase(n, M)` in phased models
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Attacker {
+    att: Identifier,
+    phased: Identifier,
+}
+impl Attacker {
+    pub fn new(id_server: &mut IdentifierServer) -> Attacker {
+        Attacker {
+            att: id_server.register_function(ATTACKER_PREDICATE),
+            phased: id_server.register_function(PHASE_PREDICATE),
+        }
+    }
+
+    /// Checks if `symbol` is one of the predicates of the attacker's knowledge
+    pub fn is_predicate(&self, symbol: Identifier) -> bool {
+        symbol == self.att || symbol == self.phased
+    }
+
+    /// Returns the message of an atom of the attacker's knowledge
+    pub fn message<'a>(&self, atom: &'a InnerAtom) -> Option<&'a InnerTerm> {
+        match &atom.parameters[..] {
+            [message] if atom.symbol == self.att => Some(message),
+            [_, message] if atom.symbol == self.phased => Some(message),
+            _ => None,
+        }
+    }
+
+    /// Checks if the atom is `att(X)` or `att_phase(n, X)`, `X` being a variable
+    pub fn knows_variable(&self, atom: &InnerAtom) -> bool {
+        self.message(atom).is_some_and(|m| m.is_variable())
+    }
+}
+
+/// A step of an attack
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceStep {
+    /// The attacker knows a message from the start
+    Knowledge(Atom<String>),
+    /// The attacker computes a message from messages it already knows, using one of its capabilities
+    Computation {
+        from: Vec<Atom<String>>,
+        result: Atom<String>,
+    },
+    /// The attacker sends messages to a protocol participant, which answers
+    Exchange {
+        sent: Vec<Atom<String>>,
+        received: Atom<String>,
+    },
+    /// A protocol participant executes an event, once it received messages from the attacker
+    Event {
+        sent: Vec<Atom<String>>,
+        event: Atom<String>,
+    },
+}
+
+/// An ordered attack trace, printed as a message sequence chart
+#[derive(Debug, Clone, Default)]
+pub struct AttackTrace {
+    pub steps: Vec<TraceStep>,
+}
+
+impl Rule<String> {
+    /// Checks if the rule is an attacker capability, meaning it only speaks of attacker
+    /// knowledge and does not mention any name, be it a constant or session-indexed: those
+    /// belong to the protocol
+    pub fn is_attacker_capability(&self) -> bool {
+        fn has_names(t: &Term<String>) -> bool {
+            match t {
+                Term::Function { parameters, .. } if parameters.is_empty() || t.is_name() => true,
+                Term::Function { parameters, .. } => parameters.iter().any(has_names),
+                Term::Variable { .. } => false,
+            }
+        }
+
+        self.premises
+            .iter()
+            .chain(std::iter::once(&self.conclusion))
+            .all(|a| a.known_message().is_some_and(|m| !has_names(m)))
+    }
+}
+
+impl Atom<String> {
+    /// Returns the message of an atom of the attacker's knowledge, `att(M)` or `att_phase(n, M)`
+    pub fn known_message(&self) -> Option<&Term<String>> {
+        match &self.parameters[..] {
+            [message] if self.symbol == ATTACKER_PREDICATE => Some(message),
+            [_, message] if self.symbol == PHASE_PREDICATE => Some(message),
+            _ => None,
+        }
+    }
+}
+
+impl DerivationTree {
+    /// Reconstructs the attack trace leading to the root of the tree
+    pub fn attack_trace(&self) -> AttackTrace {
+        let mut instantiation = Instantiation::default();
+        let root = instantiation.fresh_rule(self.root());
+        instantiation.unify_tree(self, root);
+
+        let leaves = instantiation
+            .leaves
+            .iter()
+            .map(|(rule, inner)| {
+                let instance = inner.apply_fully(&instantiation.substitution);
+                (rule, instance.to_string(&instantiation.id_server))
+            })
+            .collect_vec();
+
+        // Fires the instantiated clauses once none of their premises is left to be concluded,
+        // falling back on derivation order if they depend on one another
+        let mut known = FxHashSet::default();
+        let mut fired = vec![false; leaves.len()];
+        let mut steps = vec![];
+        let is_pending = |a: &Atom<String>, fired: &[bool]| {
+            (0..leaves.len()).any(|j| !fired[j] && &leaves[j].1.conclusion == a)
+        };
+        while let Some(i) = (0..leaves.len())
+            .find(|&i| {
+                !fired[i]
+                    && leaves[i]
+                        .1
+                        .premises
+                        .iter()
+                        .all(|p| known.contains(p) || !is_pending(p, &fired))
+            })
+            .or_else(|| (0..leaves.len()).find(|&i| !fired[i]))
+        {
+            fired[i] = true;
+            let (original, instance) = &leaves[i];
+            if !known.insert(instance.conclusion.clone()) {
+                continue;
+            }
+
+            steps.push(if original.is_attacker_capability() {
+                if instance.premises.is_empty() {
+                    TraceStep::Knowledge(instance.conclusion.clone())
+                } else {
+                    TraceStep::Computation {
+                        from: instance.premises.clone(),
+                        result: instance.conclusion.clone(),
+                    }
+                }
+            } else if instance.conclusion.symbol == EVENT_PREDICATE {
+                TraceStep::Event {
+                    sent: instance.premises.clone(),
+                    event: instance.conclusion.clone(),
+                }
+            } else if instance.premises.is_empty() {
+                TraceStep::Knowledge(instance.conclusion.clone())
+            } else {
+                TraceStep::Exchange {
+                    sent: instance.premises.clone(),
+                    received: instance.conclusion.clone(),
+                }
+            });
+        }
+
+        AttackTrace { steps }
+    }
+}
+
+/// Accumulates the unifiers of every resolution step of a derivation tree. Every node of
+/// the tree is renamed apart, so a single substitution covers all of them
+#[derive(Default)]
+struct Instantiation {
+    id_server: IdentifierServer,
+    substitution: FxHashMap<InnerTerm, InnerTerm>,
+    leaves: Vec<(Rule<String>, InnerRule)>,
+}
+impl Instantiation {
+    /// Converts a rule of the tree with fresh variables, as the same rule may occur many times
+    fn fresh_rule(&mut self, rule: &Rule<String>) -> InnerRule {
+        let mut bindings = HashMap::new();
+        Rule {
+            conclusion: rule.conclusion.to_inner(&mut self.id_server, &mut bindings),
+            premises: rule
+                .premises
+                .iter()
+                .map(|a| a.to_inner(&mut self.id_server, &mut bindings))
+                .collect(),
+            constraints: rule
+                .constraints
+                .iter()
+                .map(|c| c.to_inner(&mut self.id_server, &mut bindings))
+                .collect(),
+        }
+    }
+
+    /// Merges the unifier of a resolution step into the substitution, unless it conflicts
+    /// with the bindings of the steps already replayed
+    fn extend(&mut self, bindings: FxHashMap<InnerTerm, InnerTerm>) -> bool {
+        let mut substitution = self.substitution.clone();
+        for (k, v) in bindings {
+            let k = k.apply_fully(&substitution);
+            let v = v.apply_fully(&substitution);
+            let Some(mu) = k.unify(&v) else {
+                return false;
+            };
+            substitution.extend(mu.into_iter().filter(|(x, t)| x != t));
+        }
+        self.substitution = substitution;
+        true
+    }
+
+    /// Walks the tree from the root down, so that every node is already instantiated
+    /// by its ancestors when its own resolution step is replayed
+    fn unify_tree(&mut self, tree: &DerivationTree, node: InnerRule) {
+        if tree.subtrees().is_empty() {
+            self.leaves.push((tree.root().clone(), node));
+            return;
+        }
+
+        let children = tree
+            .subtrees()
+            .iter()
+            .map(|t| (t, self.fresh_rule(t.root())))
+            .collect_vec();
+        let premise_side = children.iter().find_map(|(t, r)| match t.selection() {
+            Some(Selection::Premise(_, i)) => Some((r, *i)),
+            _ => None,
+        });
+        let conclusion_side = children
+            .iter()
+            .find(|(t, _)| matches!(t.selection(), Some(Selection::Conclusion(_))));
+
+        if let (Some((p_rule, i)), Some((_, c_rule))) = (premise_side, conclusion_side) {
+            let p_rule = p_rule.apply_fully(&self.substitution);
+            let c_rule = c_rule.apply_fully(&self.substitution);
+            if p_rule.premises[i]
+                .unify(&c_rule.conclusion)
+                .is_some_and(|sigma| self.extend(sigma))
+            {
+                let mut premises = p_rule.premises.clone();
+                premises.remove(i);
+                premises.extend(c_rule.premises.iter().cloned());
+                let resolvent = Rule {
+                    conclusion: p_rule.conclusion.clone(),
+                    premises,
+                    constraints: vec![],
+                }
+                .apply_fully(&self.substitution);
+                let node = node.apply_fully(&self.substitution);
+                self.align(&node, &resolvent);
+            }
+        }
+
+        for (subtree, rule) in children {
+            self.unify_tree(subtree, rule)
+        }
+    }
+
+    /// Unifies a derived rule with the resolvent it was computed from, which may
+    /// contain additional premises the simplifications removed
+    fn align(&mut self, node: &InnerRule, resolvent: &InnerRule) {
+        let mut pairs = vec![(
+            Term::from(node.conclusion.clone()),
+            Term::from(resolvent.conclusion.clone()),
+        )];
+        let mut candidates = resolvent.premises.iter();
+        for premise in &node.premises {
+            if let Some(matching) = candidates.find(|c| premise.unify(c).is_some()) {
+                pairs.push((Term::from(premise.clone()), Term::from(matching.clone())));
+            }
+        }
+
+        if let Ok(mu) = InnerTerm::try_unify_all(pairs) {
+            self.extend(mu);
+        }
+    }
+}
+
+impl Atom<String> {
+    /// Returns the message of an attacker knowledge atom along with its phase if any, the event
+    /// of an event atom, or the atom itself for other predicates
+    fn message(&self) -> String {
+        match &self.parameters[..] {
+            [message] if self.symbol == ATTACKER_PREDICATE || self.symbol == EVENT_PREDICATE => {
+                message.to_string()
+            }
+            [phase, message] if self.symbol == PHASE_PREDICATE => {
+                format!("{message} (phase {phase})")
+            }
+            _ => self.to_string(),
+        }
+    }
+}
+
+impl Display for AttackTrace {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let lines = self
+            .steps
+            .iter()
+            .flat_map(|step| match step {
+                TraceStep::Knowledge(a) => vec![(' ', format!(" knows {}", a.message()), ' ')],
+                TraceStep::Computation { from, result } => vec![(
+                    ' ',
+                    format!(
+                        " computes {} from {}",
+                        result.message(),
+                        from.iter().map(|a| a.message()).join(", ")
+                    ),
+                    ' ',
+                )],
+                TraceStep::Exchange { sent, received } => sent
+                    .iter()
+                    .map(|a| ('-', format!("-- {} ", a.message()), '>'))
+                    .chain(std::iter::once((
+                        '<',
+                        format!("-- {} ", received.message()),
+                        '-',
+                    )))
+                    .collect(),
+                TraceStep::Event { sent, event } => sent
+                    .iter()
+                    .map(|a| ('-', format!("-- {} ", a.message()), '>'))
+                    .chain(std::iter::once((
+                        ' ',
+                        format!(" event {}", event.message()),
+                        ' ',
+                    )))
+                    .collect(),
+            })
+            .collect_vec();
+        let width = lines
+            .iter()
+            .map(|(_, l, _)| l.chars().count() + 4)
+            .max()
+            .unwrap_or(0)
+            .max(20);
+
+        writeln!(f, "Attacker{:>width$}", "Protocol", width = width - 3)?;
+        for (start, line, end) in lines {
+            let fill = if end == ' ' { ' ' } else { '-' };
+            let start = if start == ' ' {
+                String::new()
+            } else {
+                start.to_string()
+            };
+            let padding = width - start.len() - line.chars().count() - 1;
+            let padding = std::iter::repeat_n(fill, padding).collect::<String>();
+            writeln!(f, "   |{start}{line}{padding}{end}|")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Parser, Sniffer};
+
+    fn atom(s: &str) -> Atom<String> {
+        Parser::parse_query(s).unwrap()
+    }
+
+    #[test]
+    fn attacker_capability_test() {
+        let rules = Parser::parse_rules(
+            "att(pair(X, Y)) => att(X). att(X) => att(aenc(X, pub(ska))). att(kleak).",
+        )
+        .unwrap();
+        assert!(rules[0].is_attacker_capability());
+        assert!(!rules[1].is_attacker_capability());
+        assert!(!rules[2].is_attacker_capability());
+    }
+
+    #[test]
+    fn leak_trace_test() {
+        let mut sniffer = Sniffer::new("examples/example.pif").unwrap();
+        let Ok(tree) = sniffer.find(&atom("att(leak).")) else {
+            panic!("Expected att(leak) to be derivable")
+        };
+
+        let trace = tree.attack_trace();
+        assert_eq!(trace.steps.len(), 3);
+        assert_eq!(
+            trace.steps.last(),
+            Some(&TraceStep::Computation {
+                from: vec![atom("att(senc(leak, kleak))."), atom("att(kleak).")],
+                result: atom("att(leak)."),
+            })
+        );
+    }
+
+    #[test]
+    fn conflicting_unifier_test() {
+        let mut instantiation = Instantiation::default();
+        let mut constant = |s: &str| Term::Function {
+            symbol: instantiation.id_server.register_function(s),
+            parameters: vec![],
+        };
+        let (a, b) = (constant("a"), constant("b"));
+        let x = Term::Variable {
+            symbol: instantiation.id_server.register_variable(),
+        };
+        let y = Term::Variable {
+            symbol: instantiation.id_server.register_variable(),
+        };
+
+        assert!(instantiation.extend([(x.clone(), a.clone())].into_iter().collect()));
+        assert!(instantiation.extend([(y.clone(), x.clone())].into_iter().collect()));
+        assert_eq!(y.apply_fully(&instantiation.substitution), a);
+
+        // X is already bound to a, so binding it to b must be rejected as a whole
+        let substitution = instantiation.substitution.clone();
+        assert!(!instantiation.extend([(y.clone(), a), (x, b)].into_iter().collect()));
+        assert_eq!(instantiation.substitution, substitution);
+    }
+}
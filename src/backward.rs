@@ -0,0 +1,448 @@
+//! Backward module
+//! Goal-directed engine answering a query without saturating the whole rule set. A goal is
+//! resolved against the conclusions of the input rules, whose selected premises become subgoals
+//! in turn, each resolvent being recorded so that derivations are explained like saturated ones.
+//! Subgoals are tabled: the table of a goal gathers the solved rules concluding instances of it,
+//! which every call of the goal consumes instead of evaluating it again, and tables are
+//! evaluated again whenever a table they consume gets new answers. Deep terms of subgoals are
+//! abstracted by variables, so that there are finitely many tables
+use crate::ast::{Atom, InnerAtom, InnerRule, Rule};
+use crate::attack::Attacker;
+use crate::bound::{truncate, Bounded};
+use crate::derivation_tree::DerivationTree;
+use crate::resolution::Selection;
+use crate::trace::DiscardReason;
+use crate::{global_selection, DerivationBuilder, DerivationInfo, SaturationFailure, Sniffer};
+use itertools::Itertools;
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::collections::{HashMap, VecDeque};
+use std::str::FromStr;
+use std::time::Instant;
+
+/// Depth beyond which the terms of goals are abstracted by variables. Answers being filtered
+/// by unification, this only trades the number of tables against the answers each one gathers
+const CALL_DEPTH: usize = 2;
+
+/// Engines answering queries
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Engine {
+    /// Forward saturation of the rule set, resumed from one query to the next
+    #[default]
+    Saturation,
+    /// Resolution from the query backward to the input rules, tabling subgoals
+    Backward,
+}
+impl FromStr for Engine {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "saturation" => Ok(Engine::Saturation),
+            "backward" => Ok(Engine::Backward),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Solved rules found so far concluding instances of a goal
+struct Table {
+    // Fresh copy of the goal, which conclusions are unified with
+    goal: InnerAtom,
+    // Input rules whose conclusion unifies with the goal
+    rules: Vec<InnerRule>,
+    answers: Vec<InnerRule>,
+    // Canonical forms of the answers
+    variants: FxHashSet<InnerRule>,
+}
+
+/// Tables of the goals met, keyed by the canonical form of their abstracted goal
+#[derive(Default)]
+struct Tables {
+    tables: FxHashMap<InnerAtom, Table>,
+    // Tables called by the rules evaluated for each table, along with the selected premise
+    calls: FxHashMap<(InnerAtom, InnerRule), Option<(InnerAtom, usize)>>,
+    // Resolvents of rules with the answers of their selected premise, computed already
+    steps: FxHashMap<(InnerRule, InnerRule), Option<InnerRule>>,
+    // Rule, resolved premise, answer and removed premises every resolvent was derived from
+    origins: FxHashMap<InnerRule, (InnerRule, usize, InnerRule, Vec<usize>)>,
+    // Tables consuming the answers of each table, evaluated again when it gets new ones
+    consumers: FxHashMap<InnerAtom, FxHashSet<InnerAtom>>,
+    // Tables to evaluate
+    pending: VecDeque<InnerAtom>,
+    queued: FxHashSet<InnerAtom>,
+    // Whether resolvents were discarded for exceeding the bound
+    rejected: bool,
+}
+
+impl Tables {
+    /// Queues table `key` for evaluation, unless it is queued already
+    fn queue(&mut self, key: &InnerAtom) {
+        if self.queued.insert(key.clone()) {
+            self.pending.push_back(key.clone())
+        }
+    }
+}
+
+impl Sniffer {
+    /// Returns a derivation of `atom` if one exists, found by the given engine
+    pub fn find_with(
+        &mut self,
+        atom: &Atom<String>,
+        engine: Engine,
+    ) -> Result<DerivationTree, SaturationFailure> {
+        match engine {
+            Engine::Saturation => self.find(atom),
+            Engine::Backward => self.find_backward(atom),
+        }
+    }
+
+    /// Returns a derivation of `atom` if one exists, found by resolving it backward against the
+    /// input rules instead of saturating them. Tables are built anew for every query, and only
+    /// the steps of the derivation returned are kept, apart from the rule set
+    pub fn find_backward(
+        &mut self,
+        atom: &Atom<String>,
+    ) -> Result<DerivationTree, SaturationFailure> {
+        let start = Instant::now();
+        let goal = atom.to_inner(&mut self.id_server, &mut HashMap::new());
+        let mut tables = Tables::default();
+        let attacker = Attacker::new(&mut self.id_server);
+        let fact = self.prove(
+            &goal,
+            attacker,
+            &mut tables,
+            &mut FxHashSet::default(),
+            &mut FxHashMap::default(),
+        );
+        self.stats.backward_time += start.elapsed();
+
+        self.backward_steps.clear();
+        match fact {
+            Some(fact) => {
+                self.keep_steps(&fact, &tables);
+                Ok(self.smallest_derivation_tree(&fact, &mut DerivationBuilder::default()))
+            }
+            None if tables.rejected => Err(SaturationFailure::Incomplete),
+            None => Err(SaturationFailure::Saturated),
+        }
+    }
+
+    /// Derives a fact `atom` is an instance of, by resolving the premises of an answer of its
+    /// table with facts derived the same way, as `derive_fact` does with saturated rules
+    fn prove(
+        &mut self,
+        atom: &InnerAtom,
+        attacker: Attacker,
+        tables: &mut Tables,
+        proving: &mut FxHashSet<InnerAtom>,
+        proved: &mut FxHashMap<InnerAtom, InnerRule>,
+    ) -> Option<InnerRule> {
+        if let Some(fact) = proved.get(atom) {
+            return Some(fact.clone());
+        }
+        let key = self.complete(atom, attacker, tables);
+        let answers = tables.tables[&key]
+            .answers
+            .iter()
+            .sorted_by_key(|r| r.premises.len())
+            .cloned()
+            .collect_vec();
+        let concludes = |rule: &InnerRule| {
+            atom.unify(&rule.conclusion).is_some_and(|bindings| {
+                rule.apply_fully(&bindings).simplify_constraints().is_some()
+            })
+        };
+        if !atom.is_ground() {
+            return answers
+                .into_iter()
+                .find(|r| r.premises.is_empty() && concludes(r));
+        }
+        if !proving.insert(atom.clone()) {
+            return None;
+        }
+
+        let mut derived = None;
+        'rules: for rule in answers {
+            let mut current = rule;
+            while let Some(premise) = current.premises.first() {
+                let Some(bindings) = atom.unify(&current.conclusion) else {
+                    continue 'rules;
+                };
+                let instance = premise.apply_fully(&bindings);
+                let Some(fact) = self.prove(&instance, attacker, tables, proving, proved) else {
+                    continue 'rules;
+                };
+                match self.resolve_premise(&current, 0, &fact, attacker) {
+                    Some((resolvent, removed)) => {
+                        self.record_origin(&resolvent, &current, 0, &fact, removed, tables);
+                        current = resolvent
+                    }
+                    None => continue 'rules,
+                }
+            }
+            if concludes(&current) {
+                derived = Some(current);
+                break;
+            }
+        }
+
+        proving.remove(atom);
+        if let Some(fact) = &derived {
+            proved.insert(atom.clone(), fact.clone());
+        }
+        derived
+    }
+
+    /// Registers the table of `goal`, then evaluates the pending tables until none of them
+    /// gets new answers. Returns the key of the table of `goal`
+    fn complete(&mut self, goal: &InnerAtom, attacker: Attacker, tables: &mut Tables) -> InnerAtom {
+        let key = self.register(goal, tables);
+        while let Some(key) = tables.pending.pop_front() {
+            tables.queued.remove(&key);
+            let table = &tables.tables[&key];
+            let (goal, rules) = (table.goal.clone(), table.rules.clone());
+            for rule in rules {
+                self.expand(rule, &key, &goal, attacker, tables)
+            }
+        }
+        key
+    }
+
+    /// Returns the key of the table of `goal`, creating the table if it is new
+    fn register(&mut self, goal: &InnerAtom, tables: &mut Tables) -> InnerAtom {
+        let variables = self.id_server.variables();
+        let mut replaced = FxHashMap::default();
+        let abstracted = Rule {
+            conclusion: Atom {
+                symbol: goal.symbol,
+                parameters: goal
+                    .parameters
+                    .iter()
+                    .map(|t| truncate(t, CALL_DEPTH, &mut replaced, variables))
+                    .collect(),
+            },
+            premises: vec![],
+            constraints: vec![],
+        };
+        let key = abstracted.canonical().conclusion;
+        if !tables.tables.contains_key(&key) {
+            let goal = abstracted.make_fresh(variables).conclusion;
+            let rules = self
+                .input_rules
+                .keys()
+                .filter(|r| goal.unify(&r.conclusion).is_some())
+                .cloned()
+                .collect_vec();
+            let table = Table {
+                goal,
+                rules,
+                answers: vec![],
+                variants: FxHashSet::default(),
+            };
+            tables.tables.insert(key.clone(), table);
+            tables.queue(&key);
+        }
+        key
+    }
+
+    /// Resolves the selected premise of `rule`, instantiated by the goal of table `key`, with
+    /// every answer of its own table, until solved rules are left to answer table `key`
+    fn expand(
+        &mut self,
+        rule: InnerRule,
+        key: &InnerAtom,
+        goal: &InnerAtom,
+        attacker: Attacker,
+        tables: &mut Tables,
+    ) {
+        let call = (key.clone(), rule);
+        let (subgoal, index) = match tables.calls.get(&call) {
+            Some(Some(subgoal)) => subgoal.clone(),
+            Some(None) => return,
+            None => {
+                let subgoal = self.call(&call.1, key, goal, attacker, tables);
+                tables.calls.insert(call.clone(), subgoal.clone());
+                match subgoal {
+                    Some(subgoal) => subgoal,
+                    None => return,
+                }
+            }
+        };
+
+        let rule = call.1;
+        for answer in tables.tables[&subgoal].answers.clone() {
+            let step = (rule.clone(), answer);
+            let resolvent = match tables.steps.get(&step) {
+                Some(resolvent) => resolvent.clone(),
+                None => {
+                    let resolvent = self.bounded_step(&step.0, index, &step.1, attacker, tables);
+                    tables.steps.insert(step, resolvent.clone());
+                    resolvent
+                }
+            };
+            if let Some(resolvent) = resolvent {
+                self.expand(resolvent, key, goal, attacker, tables)
+            }
+        }
+    }
+
+    /// Returns the table of the selected premise of `rule` once instantiated by the goal of
+    /// table `key`, which consumes its answers, along with the index of the premise. Solved
+    /// rules have none, and are added to the answers of table `key` instead
+    fn call(
+        &mut self,
+        rule: &InnerRule,
+        key: &InnerAtom,
+        goal: &InnerAtom,
+        attacker: Attacker,
+        tables: &mut Tables,
+    ) -> Option<(InnerAtom, usize)> {
+        let bindings = goal.unify(&rule.conclusion)?;
+        let (premise, index) = match global_selection(attacker)(rule) {
+            Selection::Premise(premise, index) => (premise, index),
+            Selection::Conclusion(_) => {
+                let table = tables.tables.get_mut(key).unwrap();
+                if table.variants.insert(rule.canonical()) {
+                    let predicate = self.id_server.name_of(&rule.conclusion.symbol).unwrap();
+                    self.stats.record_kept(rule, predicate);
+                    table.answers.push(rule.clone());
+                    for consumer in tables.consumers.get(key).cloned().into_iter().flatten() {
+                        tables.queue(&consumer)
+                    }
+                }
+                return None;
+            }
+        };
+
+        let subgoal = self.register(&premise.apply_fully(&bindings), tables);
+        tables
+            .consumers
+            .entry(subgoal.clone())
+            .or_default()
+            .insert(key.clone());
+        Some((subgoal, index))
+    }
+
+    /// Resolves premise `index` of `rule` with `answer`, recording the step unless the resolvent
+    /// exceeds the bound
+    fn bounded_step(
+        &mut self,
+        rule: &InnerRule,
+        index: usize,
+        answer: &InnerRule,
+        attacker: Attacker,
+        tables: &mut Tables,
+    ) -> Option<InnerRule> {
+        let (resolvent, removed) = self.resolve_premise(rule, index, answer, attacker)?;
+        self.stats.generated += 1;
+        let bounded = match self.bound {
+            Some(bound) => bound.apply(resolvent, self.id_server.variables()),
+            None => Bounded::Kept(resolvent),
+        };
+        let resolvent = match bounded {
+            Bounded::Kept(r) => r,
+            Bounded::Approximated(r) => {
+                self.stats.approximated += 1;
+                self.approximated.insert(r.clone());
+                r
+            }
+            Bounded::Rejected(r) => {
+                self.stats.rejected += 1;
+                tables.rejected = true;
+                self.emit_discarded(&r, DiscardReason::OutOfBound);
+                return None;
+            }
+        };
+        self.record_origin(&resolvent, rule, index, answer, removed, tables);
+        Some(resolvent)
+    }
+
+    /// Records that `resolvent` results from resolving premise `index` of `rule` with the
+    /// conclusion of `other`, in the tables rather than the rule set
+    fn record_origin(
+        &mut self,
+        resolvent: &InnerRule,
+        rule: &InnerRule,
+        index: usize,
+        other: &InnerRule,
+        removed: Vec<usize>,
+        tables: &mut Tables,
+    ) {
+        self.emit_resolved(resolvent, rule, other);
+        tables.origins.insert(
+            resolvent.clone(),
+            (rule.clone(), index, other.clone(), removed),
+        );
+    }
+
+    /// Keeps the steps deriving `rule`, and those of its parents in turn
+    fn keep_steps(&mut self, rule: &InnerRule, tables: &Tables) {
+        let Some((parent, index, other, removed)) = tables.origins.get(rule) else {
+            return;
+        };
+        if self.backward_steps.contains_key(&rule.canonical()) {
+            return;
+        }
+        let step = DerivationInfo::step(parent, *index, other, removed.clone());
+        self.backward_steps.insert(rule.canonical(), step);
+        self.keep_steps(parent, tables);
+        self.keep_steps(other, tables);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{check, Parser};
+
+    #[test]
+    fn engines_agree_test() {
+        let rules =
+            Parser::parse_rules(&std::fs::read_to_string("examples/nspk.pif").unwrap()).unwrap();
+        let secret = Parser::parse_query("att(nb[na[ski]]).").unwrap();
+        let key = Parser::parse_query("att(ska).").unwrap();
+        let event = Parser::parse_query("event(end(pub(ska), pub(skb), nb[na[ski]])).").unwrap();
+
+        // Each engine answers on its own model, so that neither reuses what the other derived
+        let mut backward = Sniffer::new("examples/nspk.pif").unwrap();
+        let mut saturation = Sniffer::new("examples/nspk.pif").unwrap();
+        for query in [&secret, &event] {
+            let Ok(tree) = backward.find_with(query, Engine::Backward) else {
+                panic!("Expected {query} to be derivable backward")
+            };
+            assert_eq!(&tree.root().conclusion, query);
+            let certificate = backward.certificate(tree.root()).unwrap();
+            assert!(check(&certificate, &rules).is_ok());
+            assert!(saturation.find_with(query, Engine::Saturation).is_ok());
+        }
+        assert!(matches!(
+            backward.find_backward(&key),
+            Err(SaturationFailure::Saturated)
+        ));
+        assert!(saturation.find(&key).is_err());
+
+        // Backward steps stay out of the rule set
+        assert_eq!(backward.rules.len(), rules.len());
+    }
+
+    #[test]
+    fn recursive_test() {
+        // Decryption calls itself on ever deeper ciphertexts, unless subgoals are tabled
+        let source = "att(senc(X, Y)) /\\ att(Y) => att(X). att(X) /\\ att(Y) => att(senc(X, Y)).
+            att(senc(s, k)). att(senc(k, s)).";
+        let mut sniffer = Sniffer::from_str(source).unwrap();
+        let query = Parser::parse_query("att(s).").unwrap();
+        assert!(matches!(
+            sniffer.find_backward(&query),
+            Err(SaturationFailure::Saturated)
+        ));
+
+        sniffer
+            .add_rules(Parser::parse_rules("att(k).").unwrap())
+            .unwrap();
+        let Ok(tree) = sniffer.find_backward(&query) else {
+            panic!("Expected the secret to be derivable once the key is known")
+        };
+        assert_eq!(tree.root().conclusion, query);
+    }
+}
@@ -0,0 +1,154 @@
+//! Bound module
+//! Abstractions applied to resolvents so that saturation terminates on models deriving ever
+//! deeper terms, such as `att(X) => att(h(X))`, at the cost of exactness
+use crate::ast::{Atom, InnerRule, InnerTerm, Rule, Term};
+use crate::identifiers::VariableAllocator;
+use rustc_hash::FxHashMap;
+
+/// Bounds on the terms of resolvents
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    /// Resolvents with a term nested deeper than `depth`, or made of more than `size` symbols,
+    /// are discarded. Derivations are genuine, but atoms may be derivable without being derived
+    Reject { depth: usize, size: usize },
+    /// Subterms nested deeper than `depth` are replaced by fresh variables, which is a sound
+    /// over-approximation: atoms that are not derived are not derivable, but derivations may be spurious
+    Approximate { depth: usize },
+}
+
+/// A resolvent once bounded
+#[derive(Debug, PartialEq, Eq)]
+pub enum Bounded {
+    Kept(InnerRule),
+    Approximated(InnerRule),
+    Rejected(InnerRule),
+}
+
+impl Bound {
+    pub fn apply(&self, rule: InnerRule, variables: &VariableAllocator) -> Bounded {
+        let terms = || {
+            rule.premises
+                .iter()
+                .chain(std::iter::once(&rule.conclusion))
+                .flat_map(|a| &a.parameters)
+        };
+        match *self {
+            Bound::Reject { depth, size } => {
+                if terms().all(|t| t.depth() <= depth && t.size() <= size) {
+                    Bounded::Kept(rule)
+                } else {
+                    Bounded::Rejected(rule)
+                }
+            }
+            Bound::Approximate { depth } => {
+                if terms().all(|t| t.depth() <= depth) {
+                    return Bounded::Kept(rule);
+                }
+                // Equal subterms are replaced by the same variable, to lose as little as possible
+                let mut replaced = FxHashMap::default();
+                let mut truncate_atom = |a: &Atom<_>| Atom {
+                    symbol: a.symbol,
+                    parameters: a
+                        .parameters
+                        .iter()
+                        .map(|t| truncate(t, depth, &mut replaced, variables))
+                        .collect(),
+                };
+                // Dropping the constraints on deep subterms only makes the rule more general
+                Bounded::Approximated(Rule {
+                    conclusion: truncate_atom(&rule.conclusion),
+                    premises: rule.premises.iter().map(truncate_atom).collect(),
+                    constraints: rule
+                        .constraints
+                        .iter()
+                        .filter(|c| c.left.depth() <= depth && c.right.depth() <= depth)
+                        .cloned()
+                        .collect(),
+                })
+            }
+        }
+    }
+}
+
+/// Replaces the subterms of `term` nested deeper than `depth` by variables
+pub fn truncate(
+    term: &InnerTerm,
+    depth: usize,
+    replaced: &mut FxHashMap<InnerTerm, InnerTerm>,
+    variables: &VariableAllocator,
+) -> InnerTerm {
+    match term {
+        _ if term.depth() <= depth => term.clone(),
+        Term::Function { symbol, parameters } if depth > 1 => Term::Function {
+            symbol: *symbol,
+            parameters: parameters
+                .iter()
+                .map(|t| truncate(t, depth - 1, replaced, variables))
+                .collect(),
+        },
+        _ => replaced
+            .entry(term.clone())
+            .or_insert_with(|| Term::Variable {
+                symbol: variables.fresh(),
+            })
+            .clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identifiers::IdentifierServer;
+    use crate::Parser;
+
+    fn rule(source: &str, id_server: &mut IdentifierServer) -> InnerRule {
+        Parser::parse_rules(source).unwrap()[0].to_inner(id_server)
+    }
+
+    #[test]
+    fn reject_test() {
+        let mut id_server = IdentifierServer::default();
+        let bound = Bound::Reject { depth: 3, size: 4 };
+        let shallow = rule("att(h(h(X))) => att(h(X)).", &mut id_server);
+        assert_eq!(
+            bound.apply(shallow.clone(), id_server.variables()),
+            Bounded::Kept(shallow)
+        );
+        let deep = rule("att(h(h(h(X)))).", &mut id_server);
+        assert!(matches!(
+            bound.apply(deep, id_server.variables()),
+            Bounded::Rejected(_)
+        ));
+        let large = rule("att(pair(pair(a, b), c)).", &mut id_server);
+        assert!(matches!(
+            bound.apply(large, id_server.variables()),
+            Bounded::Rejected(_)
+        ));
+    }
+
+    #[test]
+    fn approximate_test() {
+        let mut id_server = IdentifierServer::default();
+        let deep = rule("att(h(h(h(a)))) => att(pair(h(h(a)), b)).", &mut id_server);
+        let Bounded::Approximated(approximated) =
+            Bound::Approximate { depth: 2 }.apply(deep, id_server.variables())
+        else {
+            panic!("Expected an approximated rule")
+        };
+
+        // The same deep subterm is replaced by the same variable
+        let approximated = approximated.to_string(&id_server);
+        let Term::Function { parameters, .. } = &approximated.premises[0].parameters[0] else {
+            panic!("Expected a function")
+        };
+        assert!(parameters[0].is_variable());
+        let Term::Function {
+            parameters: pair, ..
+        } = &approximated.conclusion.parameters[0]
+        else {
+            panic!("Expected a function")
+        };
+        assert!(pair[0].is_variable());
+        assert_eq!(pair[0], parameters[0]);
+    }
+}
@@ -0,0 +1,404 @@
+//! Checker module
+//! Proof certificates list every resolution step of a derivation, along with the unifier used.
+//! The checker re-verifies those steps from the input clauses only, without relying on
+//! the saturation engine: it merely applies substitutions and compares rules
+use crate::ast::{Atom, Disequality, Rule, Term};
+use crate::attack::ATTACKER_PREDICATE;
+use crate::phase::PHASE_PREDICATE;
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+/// A proof certificate, whose last step is the certified rule
+///
+/// Serialized as `{"steps": [...]}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Certificate {
+    pub steps: Vec<CertificateStep>,
+}
+
+/// A step of a proof certificate, referencing previous steps by their index
+///
+/// Serialized as `{"kind": "input", "rule": ...}` or `{"kind": "resolution", "rule": ...,
+/// "premise_step": ..., "premise_index": ..., "conclusion_step": ..., "unifier": {...}, "removed": [...]}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CertificateStep {
+    /// A rule of the input file, up to variable renaming
+    Input { rule: Rule<String> },
+    /// The resolution of premise `premise_index` of the rule of `premise_step` with the
+    /// conclusion of the rule of `conclusion_step`, which `unifier` makes equal.
+    /// `removed` lists the premises of the resolvent that were simplified away
+    Resolution {
+        rule: Rule<String>,
+        premise_step: usize,
+        premise_index: usize,
+        conclusion_step: usize,
+        unifier: BTreeMap<String, Term<String>>,
+        removed: Vec<usize>,
+    },
+}
+impl CertificateStep {
+    pub fn rule(&self) -> &Rule<String> {
+        match self {
+            CertificateStep::Input { rule } | CertificateStep::Resolution { rule, .. } => rule,
+        }
+    }
+}
+
+/// Reasons for a certificate to be rejected, along with the index of the faulty step
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckError {
+    EmptyCertificate,
+    UnknownInput(usize),
+    InvalidReference(usize),
+    NotAUnifier(usize),
+    InvalidRemoval(usize),
+    UnsatisfiableConstraints(usize),
+    NotTheResolvent(usize),
+}
+impl Display for CheckError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckError::EmptyCertificate => write!(f, "the certificate has no step"),
+            CheckError::UnknownInput(i) => write!(f, "step {i}: rule is not in the input file"),
+            CheckError::InvalidReference(i) => {
+                write!(f, "step {i}: references a missing step or premise")
+            }
+            CheckError::NotAUnifier(i) => {
+                write!(f, "step {i}: unifier does not unify the selected atoms")
+            }
+            CheckError::InvalidRemoval(i) => {
+                write!(
+                    f,
+                    "step {i}: removes a premise which is not trivially satisfied"
+                )
+            }
+            CheckError::UnsatisfiableConstraints(i) => {
+                write!(
+                    f,
+                    "step {i}: the constraints of the resolvent can never hold"
+                )
+            }
+            CheckError::NotTheResolvent(i) => {
+                write!(f, "step {i}: rule is not the resolvent of its parents")
+            }
+        }
+    }
+}
+
+/// Checks every step of `certificate` against the rules of the input file,
+/// returning the certified rule
+pub fn check<'a>(
+    certificate: &'a Certificate,
+    input: &[Rule<String>],
+) -> Result<&'a Rule<String>, CheckError> {
+    for (i, step) in certificate.steps.iter().enumerate() {
+        match step {
+            CertificateStep::Input { rule } => {
+                if !input.iter().any(|r| is_variant(rule, r)) {
+                    return Err(CheckError::UnknownInput(i));
+                }
+            }
+            CertificateStep::Resolution {
+                rule,
+                premise_step,
+                premise_index,
+                conclusion_step,
+                unifier,
+                removed,
+            } => {
+                if *premise_step >= i || *conclusion_step >= i {
+                    return Err(CheckError::InvalidReference(i));
+                }
+                let p = certificate.steps[*premise_step].rule();
+                let c = certificate.steps[*conclusion_step].rule();
+                let Some(selected) = p.premises.get(*premise_index) else {
+                    return Err(CheckError::InvalidReference(i));
+                };
+
+                let unifier: FxHashMap<_, _> = unifier
+                    .iter()
+                    .map(|(x, t)| (Term::Variable { symbol: x.clone() }, t.clone()))
+                    .collect();
+                if selected.apply(&unifier) != c.conclusion.apply(&unifier) {
+                    return Err(CheckError::NotAUnifier(i));
+                }
+
+                let mut premises = p.premises.clone();
+                premises.remove(*premise_index);
+                premises.extend(c.premises.iter().cloned());
+                let mut constraints = p.constraints.clone();
+                constraints.extend(c.constraints.iter().cloned());
+                let resolvent = Rule {
+                    conclusion: p.conclusion.clone(),
+                    premises,
+                    constraints,
+                }
+                .apply(&unifier);
+                let resolvent = simplify_constraints(resolvent)
+                    .ok_or(CheckError::UnsatisfiableConstraints(i))?;
+
+                let simplified =
+                    remove_premises(&resolvent, removed).ok_or(CheckError::InvalidRemoval(i))?;
+                if !is_variant(rule, &simplified) {
+                    return Err(CheckError::NotTheResolvent(i));
+                }
+            }
+        }
+    }
+
+    certificate
+        .steps
+        .last()
+        .map(|s| s.rule())
+        .ok_or(CheckError::EmptyCertificate)
+}
+
+/// Removes the given premises from `rule`, if they are all either duplicates of a kept
+/// premise, or of the form `att(X)` or `att_phase(n, X)` with `X` occurring nowhere else: those are satisfiable
+/// as the attacker knows at least one message
+fn remove_premises(rule: &Rule<String>, removed: &[usize]) -> Option<Rule<String>> {
+    let kept = Rule {
+        conclusion: rule.conclusion.clone(),
+        constraints: rule.constraints.clone(),
+        premises: rule
+            .premises
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !removed.contains(i))
+            .map(|(_, p)| p.clone())
+            .collect(),
+    };
+
+    for i in removed {
+        let removable = match rule.premises.get(*i) {
+            Some(p) if kept.premises.contains(p) => true,
+            Some(p) => match known_message(p) {
+                Some(x @ Term::Variable { .. }) => {
+                    !kept.conclusion.contains_variable(x)
+                        && !kept.premises.iter().any(|p| p.contains_variable(x))
+                        && !kept.constraints.iter().any(|c| c.contains_variable(x))
+                }
+                _ => false,
+            },
+            None => false,
+        };
+        if !removable {
+            return None;
+        }
+    }
+    Some(kept)
+}
+
+/// Message of an `att(M)` or `att_phase(n, M)` atom
+fn known_message(atom: &Atom<String>) -> Option<&Term<String>> {
+    match &atom.parameters[..] {
+        [message] if atom.symbol == ATTACKER_PREDICATE => Some(message),
+        [_, message] if atom.symbol == PHASE_PREDICATE => Some(message),
+        _ => None,
+    }
+}
+
+/// Removes the constraints of `rule` that always hold, as their sides differ by function
+/// symbols at the same position, along with duplicates. Returns `None` if one of them can
+/// never hold, as its sides are equal
+fn simplify_constraints(rule: Rule<String>) -> Option<Rule<String>> {
+    fn clash(s: &Term<String>, t: &Term<String>) -> bool {
+        match (s, t) {
+            (
+                Term::Function {
+                    symbol: f,
+                    parameters: f_params,
+                },
+                Term::Function {
+                    symbol: g,
+                    parameters: g_params,
+                },
+            ) => {
+                f != g
+                    || f_params.len() != g_params.len()
+                    || f_params.iter().zip(g_params).any(|(s, t)| clash(s, t))
+            }
+            _ => false,
+        }
+    }
+
+    let mut constraints: Vec<Disequality<String>> = vec![];
+    for c in &rule.constraints {
+        if c.left == c.right {
+            return None;
+        }
+        if !clash(&c.left, &c.right) && !constraints.contains(c) {
+            constraints.push(c.clone())
+        }
+    }
+    Some(Rule {
+        constraints,
+        ..rule
+    })
+}
+
+/// Checks if two rules are equal up to a renaming of their variables
+pub fn is_variant(r1: &Rule<String>, r2: &Rule<String>) -> bool {
+    let mut renaming = FxHashMap::default();
+    r1.premises.len() == r2.premises.len()
+        && r1.constraints.len() == r2.constraints.len()
+        && atoms_variant(&r1.conclusion, &r2.conclusion, &mut renaming)
+        && r1
+            .premises
+            .iter()
+            .zip(&r2.premises)
+            .all(|(a, b)| atoms_variant(a, b, &mut renaming))
+        && r1.constraints.iter().zip(&r2.constraints).all(|(c, d)| {
+            terms_variant(&c.left, &d.left, &mut renaming)
+                && terms_variant(&c.right, &d.right, &mut renaming)
+        })
+        && {
+            // The renaming must be injective as well
+            let mut images: Vec<_> = renaming.values().collect();
+            images.sort();
+            images.windows(2).all(|w| w[0] != w[1])
+        }
+}
+
+/// Extends `renaming` so that it maps atom `a` onto atom `b`, if possible
+fn atoms_variant<'a>(
+    a: &'a Atom<String>,
+    b: &'a Atom<String>,
+    renaming: &mut FxHashMap<&'a str, &'a str>,
+) -> bool {
+    a.symbol == b.symbol
+        && a.parameters.len() == b.parameters.len()
+        && a.parameters
+            .iter()
+            .zip(&b.parameters)
+            .all(|(t, u)| terms_variant(t, u, renaming))
+}
+
+fn terms_variant<'a>(
+    t: &'a Term<String>,
+    u: &'a Term<String>,
+    renaming: &mut FxHashMap<&'a str, &'a str>,
+) -> bool {
+    match (t, u) {
+        (Term::Variable { symbol: x }, Term::Variable { symbol: y }) => {
+            *renaming.entry(x.as_str()).or_insert(y.as_str()) == y.as_str()
+        }
+        (
+            Term::Function {
+                symbol: f,
+                parameters: f_params,
+            },
+            Term::Function {
+                symbol: g,
+                parameters: g_params,
+            },
+        ) => {
+            f == g
+                && f_params.len() == g_params.len()
+                && f_params
+                    .iter()
+                    .zip(g_params)
+                    .all(|(t, u)| terms_variant(t, u, renaming))
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    fn rules(s: &str) -> Vec<Rule<String>> {
+        Parser::parse_rules(s).unwrap()
+    }
+
+    fn leak_certificate(unifier: &[(&str, &str)]) -> Certificate {
+        let input = rules("att(senc(X, Y)) /\\ att(Y) => att(X). att(senc(leak, kleak)).");
+        Certificate {
+            steps: vec![
+                CertificateStep::Input {
+                    rule: input[0].clone(),
+                },
+                CertificateStep::Input {
+                    rule: input[1].clone(),
+                },
+                CertificateStep::Resolution {
+                    rule: rules("att(kleak) => att(leak).").remove(0),
+                    premise_step: 0,
+                    premise_index: 0,
+                    conclusion_step: 1,
+                    unifier: unifier
+                        .iter()
+                        .map(|(x, t)| {
+                            (
+                                x.to_string(),
+                                Term::Function {
+                                    symbol: t.to_string(),
+                                    parameters: vec![],
+                                },
+                            )
+                        })
+                        .collect(),
+                    removed: vec![],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn variant_test() {
+        let r = rules(
+            "att(pair(X, Y)) => att(X). att(pair(A, B)) => att(A). att(pair(A, A)) => att(A).",
+        );
+        assert!(is_variant(&r[0], &r[1]));
+        assert!(!is_variant(&r[0], &r[2]));
+        assert!(!is_variant(&r[2], &r[0]));
+    }
+
+    #[test]
+    fn check_valid_certificate_test() {
+        let certificate = leak_certificate(&[("X", "leak"), ("Y", "kleak")]);
+        let input = rules("att(senc(A, B)) /\\ att(B) => att(A). att(senc(leak, kleak)).");
+        assert!(check(&certificate, &input).is_ok());
+    }
+
+    #[test]
+    fn check_wrong_unifier_test() {
+        let certificate = leak_certificate(&[("X", "leak"), ("Y", "leak")]);
+        let input = rules("att(senc(A, B)) /\\ att(B) => att(A). att(senc(leak, kleak)).");
+        assert_eq!(check(&certificate, &input), Err(CheckError::NotAUnifier(2)));
+    }
+
+    #[test]
+    fn check_constraints_test() {
+        // The constraint of the decryption rule becomes leak <> leak once resolved
+        let mut certificate = leak_certificate(&[("X", "leak"), ("Y", "kleak")]);
+        let decryption = rules("att(senc(X, Y)) /\\ att(Y) /\\ X <> leak => att(X).").remove(0);
+        certificate.steps[0] = CertificateStep::Input {
+            rule: decryption.clone(),
+        };
+        let input = vec![decryption, rules("att(senc(leak, kleak)).").remove(0)];
+        assert_eq!(
+            check(&certificate, &input),
+            Err(CheckError::UnsatisfiableConstraints(2))
+        );
+
+        let resolvent = rules("att(kleak) /\\ f(a) <> g(a) /\\ X <> b /\\ X <> b => att(X).");
+        let simplified = simplify_constraints(resolvent[0].clone()).unwrap();
+        assert_eq!(simplified.to_string(), "att(kleak) /\\ X <> b => att(X)");
+    }
+
+    #[test]
+    fn check_unknown_input_test() {
+        let certificate = leak_certificate(&[("X", "leak"), ("Y", "kleak")]);
+        let input = rules("att(senc(leak, kleak)).");
+        assert_eq!(
+            check(&certificate, &input),
+            Err(CheckError::UnknownInput(0))
+        );
+    }
+}
@@ -0,0 +1,110 @@
+//! Constraints module
+//! Disequality constraints `s <> t` restrict the instances of a rule, such as an agent `X`
+//! not being `a`. They are simplified after each substitution: a constraint whose sides became
+//! equal is unsatisfiable, and one whose sides can never be equal always holds
+use crate::ast::{Disequality, Rule, Term};
+use std::fmt::Debug;
+use std::hash::Hash;
+
+impl<T: Clone + Hash + Eq + PartialEq + Debug> Disequality<T> {
+    /// Checks if the constraint holds whatever the values of its variables, as its sides
+    /// differ by function symbols at the same position
+    pub fn always_holds(&self) -> bool {
+        clash(&self.left, &self.right)
+    }
+
+    /// Checks if the constraint can never hold, as its sides are equal
+    pub fn never_holds(&self) -> bool {
+        self.left == self.right
+    }
+}
+
+impl<T: Clone + Hash + Eq + PartialEq + Debug> Rule<T> {
+    /// Simplifies the constraints of the rule, removing those that always hold along with
+    /// duplicates, or returns `None` if one of them can never hold
+    pub fn simplify_constraints(mut self) -> Option<Rule<T>> {
+        if self.constraints.iter().any(|c| c.never_holds()) {
+            return None;
+        }
+        let mut kept: Vec<Disequality<T>> = vec![];
+        for c in self.constraints {
+            if !c.always_holds() && !kept.contains(&c) {
+                kept.push(c)
+            }
+        }
+        self.constraints = kept;
+        Some(self)
+    }
+}
+
+/// Checks if two terms have different function symbols at the same position, so that no
+/// substitution makes them equal
+fn clash<T: PartialEq>(s: &Term<T>, t: &Term<T>) -> bool {
+    match (s, t) {
+        (
+            Term::Function {
+                symbol: f,
+                parameters: f_params,
+            },
+            Term::Function {
+                symbol: g,
+                parameters: g_params,
+            },
+        ) => {
+            f != g
+                || f_params.len() != g_params.len()
+                || f_params.iter().zip(g_params).any(|(s, t)| clash(s, t))
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::InnerRule;
+    use crate::identifiers::IdentifierServer;
+    use crate::resolution::Selection;
+    use crate::Parser;
+
+    fn rule(source: &str, id_server: &mut IdentifierServer) -> InnerRule {
+        Parser::parse_rules(source).unwrap()[0].to_inner(id_server)
+    }
+
+    #[test]
+    fn simplify_test() {
+        let mut id_server = IdentifierServer::default();
+        let holds = rule("att(X) /\\ f(X, b) <> f(a, c) => att(X).", &mut id_server);
+        assert!(holds.simplify_constraints().unwrap().constraints.is_empty());
+
+        let duplicate = rule("att(X) /\\ X <> a /\\ X <> a => att(X).", &mut id_server);
+        assert_eq!(
+            duplicate.simplify_constraints().unwrap().constraints.len(),
+            1
+        );
+
+        let unsatisfiable = rule("att(X) /\\ h(a) <> h(a) => att(X).", &mut id_server);
+        assert!(unsatisfiable.simplify_constraints().is_none());
+    }
+
+    #[test]
+    fn resolution_test() {
+        // Agent b only answers agents other than itself
+        let mut id_server = IdentifierServer::default();
+        let answer = rule("agent(X) /\\ X <> b => att(hello(X)).", &mut id_server);
+        let select = |r: &InnerRule| match r.premises.first() {
+            Some(p) => Selection::Premise(p.clone(), 0),
+            None => Selection::Conclusion(r.conclusion.clone()),
+        };
+
+        let a = rule("agent(a).", &mut id_server);
+        let resolvent = answer.resolve(&a, select).unwrap();
+        assert!(resolvent.constraints.is_empty());
+
+        let b = rule("agent(b).", &mut id_server);
+        assert!(answer.resolve(&b, select).is_none());
+
+        let any = rule("agent(Y).", &mut id_server);
+        let resolvent = answer.resolve(&any, select).unwrap();
+        assert_eq!(resolvent.constraints.len(), 1);
+    }
+}
@@ -0,0 +1,302 @@
+//! Correspondence module
+//! Correspondence queries, stating that whenever an event is derivable, another one is as well,
+//! such as `event(end(X, Y, N)) => event(begin(X, Y, N))` for authentication. They are checked
+//! against the saturated rule set: every derivable atom is derived by its solved rules
+use crate::ast::{Atom, InnerAtom, InnerRule, InnerTerm, Rule, Term};
+use crate::attack::Attacker;
+use crate::derivation_tree::DerivationTree;
+use crate::resolution::Selection;
+use crate::{global_selection, Sniffer, Strategy};
+use itertools::Itertools;
+use rustc_hash::FxHashMap;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+/// Symbol of the predicate representing the events protocol participants execute
+pub const EVENT_PREDICATE: &str = "event";
+
+/// A correspondence query: every derivable instance of `event` must come with the matching
+/// instance of `required`, whose variables all appear in `event`. Injective correspondences
+/// also require distinct instances of `event` to come with distinct instances of `required`,
+/// which session-indexed names in events make meaningful
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Correspondence {
+    pub event: Atom<String>,
+    pub required: Atom<String>,
+    pub injective: bool,
+}
+impl Display for Correspondence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.injective {
+            write!(f, "injective ")?;
+        }
+        write!(f, "{} => {}", self.event, self.required)
+    }
+}
+
+/// An instance of the event of a correspondence derivable without the required one
+#[derive(Clone)]
+pub struct Violation {
+    /// Solved rule deriving the instance of the event, whose premises the attacker fulfils
+    pub instance: Rule<String>,
+    pub required: Atom<String>,
+    pub derivation: DerivationTree,
+    /// Whether solved rules conclude atoms unifying with the required one: only single steps
+    /// are checked to entail it, so it may be derivable through them all the same
+    pub possibly_spurious: bool,
+}
+
+/// Two distinct instances of the event of an injective correspondence coming with the same
+/// instance of the required one
+#[derive(Clone)]
+pub struct InjectivityViolation {
+    /// Solved rules deriving the instances of the event
+    pub instances: (Rule<String>, Rule<String>),
+    pub required: Atom<String>,
+}
+
+/// Outcome of a correspondence query
+#[derive(Clone)]
+pub enum CorrespondenceResult {
+    /// Every derivable instance of the event comes with the required one
+    Holds,
+    /// The saturation discarded resolvents exceeding the bound, so instances of the event may be missed
+    Incomplete,
+    /// An instance of the event is derivable without the required one
+    Violated(Box<Violation>),
+    /// The correspondence holds, but is injective while two instances of the event come with
+    /// the same instance of the required one
+    NotInjective(Box<InjectivityViolation>),
+}
+
+impl Sniffer {
+    /// Saturates the rule set, then checks the correspondence against every solved rule
+    /// concluding an instance of its event
+    pub fn check_correspondence(&mut self, query: &Correspondence) -> CorrespondenceResult {
+        self.saturate();
+        let attacker = Attacker::new(&mut self.id_server);
+        let select = global_selection(attacker);
+        let state = &self.saturations[&Strategy::Global];
+        let solved = state
+            .processed
+            .iter()
+            .filter(|r| matches!(select(r), Selection::Conclusion(_)))
+            .sorted_by_key(|r| (r.premises.len(), r.to_string(&self.id_server).to_string()))
+            .cloned()
+            .collect_vec();
+        let rejected = state.rejected;
+
+        let symbol = self.id_server.register_function(&query.event.symbol);
+        let deriving = solved
+            .iter()
+            .filter(|r| r.conclusion.symbol == symbol)
+            .collect_vec();
+        for rule in &deriving {
+            let Some((instance, required)) = self.instantiate(query, rule) else {
+                continue;
+            };
+            if solved.iter().any(|s| self.entails(s, &required, &instance)) {
+                continue;
+            }
+
+            let variables = self.id_server.variables().scratch();
+            let possibly_spurious = solved.iter().any(|s| {
+                required
+                    .unify(&s.make_fresh(&variables).conclusion)
+                    .is_some()
+            });
+            let root = rule.to_string(&self.id_server);
+            let derivation = self.derivation_trees_of(rule, &root, 1).pop().unwrap();
+            return CorrespondenceResult::Violated(Box::new(Violation {
+                instance: instance.to_string(&self.id_server),
+                required: required.to_string(&self.id_server),
+                derivation,
+                possibly_spurious,
+            }));
+        }
+
+        // Distinct instances of the event must not be able to come with the same required one
+        if query.injective {
+            for (i, r1) in deriving.iter().enumerate() {
+                for r2 in &deriving[i..] {
+                    let (Some((i1, q1)), Some((i2, q2))) =
+                        (self.instantiate(query, r1), self.instantiate(query, r2))
+                    else {
+                        continue;
+                    };
+                    let Some(tau) = q1.unify(&q2) else {
+                        continue;
+                    };
+                    let (i1, i2) = (i1.apply_fully(&tau), i2.apply_fully(&tau));
+                    if i1.conclusion != i2.conclusion {
+                        return CorrespondenceResult::NotInjective(Box::new(
+                            InjectivityViolation {
+                                instances: (
+                                    i1.to_string(&self.id_server),
+                                    i2.to_string(&self.id_server),
+                                ),
+                                required: q1.apply_fully(&tau).to_string(&self.id_server),
+                            },
+                        ));
+                    }
+                }
+            }
+        }
+
+        if rejected {
+            CorrespondenceResult::Incomplete
+        } else {
+            CorrespondenceResult::Holds
+        }
+    }
+
+    /// Instantiates a fresh copy of `rule` so that it concludes an instance of the event of
+    /// `query`, along with the matching instance of the required event
+    fn instantiate(
+        &mut self,
+        query: &Correspondence,
+        rule: &InnerRule,
+    ) -> Option<(InnerRule, InnerAtom)> {
+        let mut bindings = HashMap::new();
+        let event = query.event.to_inner(&mut self.id_server, &mut bindings);
+        let required = query.required.to_inner(&mut self.id_server, &mut bindings);
+        let rule = rule.make_fresh(self.id_server.variables());
+        let sigma = event.unify(&rule.conclusion)?;
+        let instance = rule.apply_fully(&sigma).simplify_constraints()?;
+        Some((instance, required.apply_fully(&sigma)))
+    }
+
+    /// Checks if the solved rule `solved` derives `required` whenever the premises of `instance`
+    /// hold, its premises being either among those or ground derivable atoms, and its constraints
+    /// either always holding or among those of `instance`
+    fn entails(&mut self, solved: &InnerRule, required: &InnerAtom, instance: &InnerRule) -> bool {
+        let mut theta = FxHashMap::default();
+        if solved.conclusion.symbol != required.symbol
+            || !solved
+                .conclusion
+                .parameters
+                .iter()
+                .zip(&required.parameters)
+                .all(|(p, t)| match_term(p, t, &mut theta))
+        {
+            return false;
+        }
+        let constrained = solved.constraints.iter().all(|c| {
+            let c = c.apply_fully(&theta);
+            c.always_holds() || instance.constraints.contains(&c)
+        });
+        constrained
+            && solved.premises.iter().all(|p| {
+                let p = p.apply_fully(&theta);
+                instance.premises.contains(&p) || (p.is_ground() && self.lookup(&p).is_some())
+            })
+    }
+}
+
+/// Extends `theta` so that it maps `pattern` onto `term`, whose variables are left as is
+fn match_term(
+    pattern: &InnerTerm,
+    term: &InnerTerm,
+    theta: &mut FxHashMap<InnerTerm, InnerTerm>,
+) -> bool {
+    match (pattern, term) {
+        (Term::Variable { .. }, _) => {
+            theta.entry(pattern.clone()).or_insert_with(|| term.clone()) == term
+        }
+        (
+            Term::Function {
+                symbol: f,
+                parameters: f_params,
+            },
+            Term::Function {
+                symbol: g,
+                parameters: g_params,
+            },
+        ) => {
+            f == g
+                && f_params.len() == g_params.len()
+                && f_params
+                    .iter()
+                    .zip(g_params)
+                    .all(|(p, t)| match_term(p, t, theta))
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checker::is_variant;
+    use crate::Parser;
+    use std::str::FromStr;
+
+    fn correspondence(source: &str) -> Correspondence {
+        Parser::parse_correspondence(source).unwrap()
+    }
+
+    #[test]
+    fn authentication_holds_test() {
+        let mut sniffer = Sniffer::new("examples/nspk-ab.pif").unwrap();
+        let mut query = correspondence("event(end(X, Y, N)) => event(begin(X, Y, N)).");
+        assert!(matches!(
+            sniffer.check_correspondence(&query),
+            CorrespondenceResult::Holds
+        ));
+        query.injective = true;
+        assert!(matches!(
+            sniffer.check_correspondence(&query),
+            CorrespondenceResult::Holds
+        ));
+    }
+
+    #[test]
+    fn lowe_attack_test() {
+        // b ends a session with a whose nonce a sent to the dishonest i
+        let mut sniffer = Sniffer::new("examples/nspk.pif").unwrap();
+        let query = correspondence("event(end(X, Y, N)) => event(begin(X, Y, N)).");
+        let CorrespondenceResult::Violated(violation) = sniffer.check_correspondence(&query) else {
+            panic!("Expected the correspondence to be violated")
+        };
+        assert_eq!(violation.instance.conclusion.symbol, EVENT_PREDICATE);
+        assert_eq!(violation.required.symbol, EVENT_PREDICATE);
+        assert!(is_variant(violation.derivation.root(), &violation.instance));
+    }
+
+    #[test]
+    fn possibly_spurious_test() {
+        let query = correspondence("event(end(X)) => event(begin(X)).");
+        let mut sniffer = Sniffer::from_str("att(a). att(X) => event(end(X)).").unwrap();
+        let CorrespondenceResult::Violated(violation) = sniffer.check_correspondence(&query) else {
+            panic!("Expected the correspondence to be violated")
+        };
+        assert!(!violation.possibly_spurious);
+
+        // begin(X) also needs att(Y), which is not a premise of end(X) though always derivable
+        let mut sniffer = Sniffer::from_str(
+            "att(a). att(X) => event(end(X)). att(X) /\\ att(Y) => event(begin(X)).",
+        )
+        .unwrap();
+        let CorrespondenceResult::Violated(violation) = sniffer.check_correspondence(&query) else {
+            panic!("Expected the correspondence to be violated")
+        };
+        assert!(violation.possibly_spurious);
+    }
+
+    #[test]
+    fn not_injective_test() {
+        // Every session of b ends with the same event of a
+        let mut sniffer =
+            Sniffer::from_str("att(k). att(X) => event(end(X, s)). event(begin(s)).").unwrap();
+        let mut query = correspondence("event(end(X, N)) => event(begin(N)).");
+        assert!(matches!(
+            sniffer.check_correspondence(&query),
+            CorrespondenceResult::Holds
+        ));
+        query.injective = true;
+        assert!(matches!(
+            sniffer.check_correspondence(&query),
+            CorrespondenceResult::NotInjective(_)
+        ));
+    }
+}
@@ -1,29 +1,206 @@
 use crate::ast::Rule;
 use crate::resolution::Selection;
 use ptree::{Style, TreeItem};
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
+use std::fmt::{Display, Formatter};
 use std::io::Write;
+use std::rc::Rc;
 
-#[derive(Clone)]
+/// Where the rule of a leaf comes from
+///
+/// Serialized as `{"kind": "input", "file": ..., "line": ...}`, `{"kind": "added"}` or `{"kind": "cycle"}`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Origin {
+    /// A rule of the input, from the given line of the given file
+    Input { file: Option<String>, line: usize },
+    /// A rule added to the model programmatically, through `Sniffer::add_rule`
+    Added,
+    /// A rule whose derivation loops back onto itself, which is therefore not unfolded
+    Cycle,
+}
+impl Display for Origin {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Origin::Input {
+                file: Some(file),
+                line,
+            } => write!(f, "input clause from {file}:{line}"),
+            Origin::Input { file: None, line } => write!(f, "input clause from line {line}"),
+            Origin::Added => write!(f, "input clause added to the model"),
+            Origin::Cycle => write!(f, "cyclic derivation"),
+        }
+    }
+}
+
+/// A derivation, whose nodes may be shared between many parents when a rule is used many times
+///
+/// Serialized as `{"rule": ..., "origin": ..., "selection": ..., "subtrees": [...]}`, where
+/// `selection` is the atom of `rule` that was resolved upon to derive the parent rule
+/// (`null` at the root), `origin` is only present at leaves and `approximated` on rules
+/// approximated because of a bound. Shared nodes are repeated
+#[derive(Clone, Serialize, Deserialize)]
 pub struct DerivationTree {
-    root: Rule<String>,
-    subtrees: Vec<DerivationTree>,
+    #[serde(flatten)]
+    node: Rc<DerivationNode>,
     selection: Option<Selection<String>>,
 }
+#[derive(Clone, Serialize, Deserialize)]
+struct DerivationNode {
+    rule: Rule<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    origin: Option<Origin>,
+    /// Whether the rule is an over-approximation of the resolvent of its subtrees
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    approximated: bool,
+    subtrees: Vec<DerivationTree>,
+}
 impl DerivationTree {
     pub fn new(root: Rule<String>) -> DerivationTree {
         DerivationTree {
-            root,
-            subtrees: vec![],
+            node: Rc::new(DerivationNode {
+                rule: root,
+                origin: None,
+                approximated: false,
+                subtrees: vec![],
+            }),
             selection: None,
         }
     }
     pub fn add_subtree(&mut self, subtree: DerivationTree) {
-        self.subtrees.push(subtree)
+        Rc::make_mut(&mut self.node).subtrees.push(subtree)
     }
     pub fn set_selection(&mut self, selection: Selection<String>) {
         self.selection = Some(selection)
     }
+    pub fn set_origin(&mut self, origin: Origin) {
+        Rc::make_mut(&mut self.node).origin = Some(origin)
+    }
+    pub fn set_approximated(&mut self) {
+        Rc::make_mut(&mut self.node).approximated = true
+    }
+
+    pub fn root(&self) -> &Rule<String> {
+        &self.node.rule
+    }
+    pub fn subtrees(&self) -> &[DerivationTree] {
+        &self.node.subtrees
+    }
+    pub fn selection(&self) -> Option<&Selection<String>> {
+        self.selection.as_ref()
+    }
+    pub fn origin(&self) -> Option<&Origin> {
+        self.node.origin.as_ref()
+    }
+    pub fn is_approximated(&self) -> bool {
+        self.node.approximated
+    }
+
+    /// Checks if the derivation goes through an approximated rule, in which case the
+    /// atoms it derives may not be derivable
+    pub fn is_possibly_spurious(&self) -> bool {
+        self.is_approximated() || self.subtrees().iter().any(|t| t.is_possibly_spurious())
+    }
+
+    /// Checks if both trees share the same root node
+    pub fn shares_root(&self, other: &DerivationTree) -> bool {
+        Rc::ptr_eq(&self.node, &other.node)
+    }
+
+    /// Renders the derivation as a Graphviz DOT graph, with one node per rule and edges
+    /// going from a rule to the one it helped derive, labelled with the selected atom
+    pub fn to_dot(&self) -> String {
+        fn escape(s: &str) -> String {
+            s.replace('\\', "\\\\").replace('"', "\\\"")
+        }
+
+        fn write_node(
+            tree: &DerivationTree,
+            ids: &mut FxHashMap<*const DerivationNode, usize>,
+            out: &mut String,
+        ) -> usize {
+            if let Some(id) = ids.get(&Rc::as_ptr(&tree.node)) {
+                return *id;
+            }
+            let id = ids.len();
+            ids.insert(Rc::as_ptr(&tree.node), id);
+
+            let mut label = escape(&tree.root().to_string());
+            if let Some(origin) = tree.origin() {
+                label = format!("{label}\\n{}", escape(&origin.to_string()));
+            }
+            out.push_str(&format!("    n{id} [label=\"{label}\"];\n"));
+            for subtree in tree.subtrees() {
+                let child = write_node(subtree, ids, out);
+                let label = subtree
+                    .selection
+                    .as_ref()
+                    .map(|s| escape(&s.atom().to_string()))
+                    .unwrap_or_default();
+                out.push_str(&format!("    n{child} -> n{id} [label=\"{label}\"];\n"));
+            }
+            id
+        }
+
+        let mut out = String::from("digraph derivation {\n");
+        out.push_str("    rankdir=BT;\n");
+        out.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+        write_node(self, &mut FxHashMap::default(), &mut out);
+        out.push_str("}\n");
+        out
+    }
+
+    /// Renders the tree as a standalone LaTeX document, using `bussproofs` to typeset
+    /// the derivation as a proof tree
+    pub fn to_latex(&self) -> String {
+        const INFERENCES: [&str; 6] = [
+            "AxiomC",
+            "UnaryInfC",
+            "BinaryInfC",
+            "TrinaryInfC",
+            "QuaternaryInfC",
+            "QuinaryInfC",
+        ];
+
+        // bussproofs expects the tree in post-order, leaves first
+        fn write_node(tree: &DerivationTree, out: &mut String) {
+            let inference = INFERENCES[write_premises(tree.subtrees(), out)];
+            out.push_str(&format!(
+                "  \\{inference}{{${}$}}\n",
+                tree.root().selection_empathized_latex(tree.selection())
+            ));
+        }
+
+        // An inference has at most five premises, so more of them are gathered in groups under
+        // unlabelled inferences without a line. Returns the number of premises left
+        fn write_premises(trees: &[DerivationTree], out: &mut String) -> usize {
+            if trees.len() < INFERENCES.len() {
+                trees.iter().for_each(|t| write_node(t, out));
+                return trees.len();
+            }
+            let groups = trees.chunks(trees.len().div_ceil(INFERENCES.len() - 1));
+            for group in groups.clone() {
+                let premises = write_premises(group, out);
+                if premises > 1 {
+                    out.push_str(&format!("  \\noLine\n  \\{}{{}}\n", INFERENCES[premises]));
+                }
+            }
+            groups.len()
+        }
+
+        let mut out = String::from("\\documentclass{article}\n");
+        out.push_str("\\usepackage{amsmath}\n");
+        out.push_str("\\usepackage{bussproofs}\n");
+        out.push_str("\\usepackage{xcolor}\n");
+        out.push_str("\\begin{document}\n");
+        out.push_str("\\begin{prooftree}\n");
+        write_node(self, &mut out);
+        out.push_str("\\end{prooftree}\n");
+        out.push_str("\\end{document}\n");
+        out
+    }
 }
 impl TreeItem for DerivationTree {
     type Child = Self;
@@ -32,13 +209,171 @@ impl TreeItem for DerivationTree {
             write!(
                 f,
                 "{}",
-                style.paint(&self.root.selection_empathized_string(selection.clone()))
-            )
+                style.paint(&self.root().selection_empathized_string(selection.clone()))
+            )?;
         } else {
-            write!(f, "{}", style.paint(&self.root.to_string()))
+            write!(f, "{}", style.paint(&self.root().to_string()))?;
+        }
+        if let Some(origin) = self.origin() {
+            write!(f, "  ({origin})")?;
+        }
+        if self.is_approximated() {
+            write!(f, "  (approximated)")?;
         }
+        Ok(())
     }
-    fn children(&self) -> Cow<[Self::Child]> {
-        Cow::from(self.subtrees.clone())
+    fn children(&self) -> Cow<'_, [Self::Child]> {
+        Cow::Borrowed(self.subtrees())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{Atom, Term};
+
+    #[test]
+    fn json_round_trip_test() {
+        let leak = Atom {
+            symbol: String::from("att"),
+            parameters: vec![Term::Function {
+                symbol: String::from("leak"),
+                parameters: vec![],
+            }],
+        };
+        let mut subtree = DerivationTree::new(Rule {
+            premises: vec![],
+            conclusion: leak.clone(),
+            constraints: vec![],
+        });
+        subtree.set_selection(Selection::Conclusion(leak.clone()));
+        let mut tree = DerivationTree::new(Rule {
+            premises: vec![],
+            conclusion: leak,
+            constraints: vec![],
+        });
+        tree.add_subtree(subtree);
+
+        let json = serde_json::to_value(&tree).unwrap();
+        assert_eq!(json["rule"]["conclusion"]["symbol"], "att");
+        assert_eq!(
+            json["rule"]["conclusion"]["parameters"][0]["kind"],
+            "function"
+        );
+        assert!(json["selection"].is_null());
+        assert_eq!(json["subtrees"][0]["selection"]["kind"], "conclusion");
+
+        let parsed: DerivationTree = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), json);
+    }
+
+    #[test]
+    fn dot_test() {
+        let quoted = Atom {
+            symbol: String::from("att"),
+            parameters: vec![Term::Function {
+                symbol: String::from("say\"hi\""),
+                parameters: vec![],
+            }],
+        };
+        let mut leaf = DerivationTree::new(Rule {
+            premises: vec![],
+            conclusion: quoted.clone(),
+            constraints: vec![],
+        });
+        leaf.set_selection(Selection::Conclusion(quoted.clone()));
+        leaf.set_origin(Origin::Added);
+        let mut tree = DerivationTree::new(Rule {
+            premises: vec![],
+            conclusion: quoted,
+            constraints: vec![],
+        });
+        tree.add_subtree(leaf.clone());
+        tree.add_subtree(leaf);
+
+        let dot = tree.to_dot();
+        assert!(dot.starts_with("digraph derivation {\n"));
+        assert!(dot.ends_with("}\n"));
+        assert!(dot.contains("n0 [label=\"att(say\\\"hi\\\")\"];"));
+        assert!(
+            dot.contains("n1 [label=\"att(say\\\"hi\\\")\\ninput clause added to the model\"];")
+        );
+        // The shared leaf is written once, with an edge per use
+        assert!(!dot.contains("n2 "));
+        assert_eq!(
+            dot.matches("n1 -> n0 [label=\"att(say\\\"hi\\\")\"];")
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn latex_many_premises_test() {
+        let leaf = |i: usize| {
+            DerivationTree::new(Rule {
+                premises: vec![],
+                conclusion: Atom {
+                    symbol: format!("p{i}"),
+                    parameters: vec![],
+                },
+                constraints: vec![],
+            })
+        };
+        let mut tree = leaf(0);
+        (1..=7).for_each(|i| tree.add_subtree(leaf(i)));
+
+        // Seven premises are split in groups of two, the last one alone
+        let latex = tree.to_latex();
+        assert_eq!(latex.matches("\\AxiomC").count(), 7);
+        assert_eq!(latex.matches("\\noLine\n  \\BinaryInfC{}").count(), 3);
+        assert!(latex.contains("  \\QuaternaryInfC{$\\mathsf{p0}$}"));
+    }
+
+    #[test]
+    fn latex_test() {
+        let atom = |symbol: &str| Atom {
+            symbol: String::from("att"),
+            parameters: vec![Term::Function {
+                symbol: String::from(symbol),
+                parameters: vec![],
+            }],
+        };
+        let mut leaf = DerivationTree::new(Rule {
+            premises: vec![],
+            conclusion: atom("my_key"),
+            constraints: vec![],
+        });
+        leaf.set_selection(Selection::Conclusion(atom("my_key")));
+        let mut tree = DerivationTree::new(Rule {
+            premises: vec![atom("a\\b{c}")],
+            conclusion: atom("my_key"),
+            constraints: vec![],
+        });
+        tree.set_selection(Selection::Premise(atom("a\\b{c}"), 0));
+        let mut root = DerivationTree::new(Rule {
+            premises: vec![],
+            conclusion: atom("my_key"),
+            constraints: vec![],
+        });
+        root.add_subtree(leaf);
+        root.add_subtree(tree);
+
+        let latex = root.to_latex();
+        assert!(latex.contains("\\usepackage{bussproofs}"));
+        let proof: Vec<_> = latex
+            .lines()
+            .skip_while(|l| *l != "\\begin{prooftree}")
+            .collect();
+        assert_eq!(
+            proof,
+            [
+                "\\begin{prooftree}",
+                "  \\AxiomC{$\\textcolor{red}{\\boldsymbol{\\mathsf{att}(\\mathsf{my\\_key})}}$}",
+                "  \\AxiomC{$\\textcolor{red}{\\boldsymbol{\\mathsf{att}(\\mathsf{a\\backslash{}b\\{c\\}})}} \\Rightarrow \\mathsf{att}(\\mathsf{my\\_key})$}",
+                "  \\BinaryInfC{$\\mathsf{att}(\\mathsf{my\\_key})$}",
+                "\\end{prooftree}",
+                "\\end{document}",
+            ]
+        );
     }
 }
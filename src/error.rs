@@ -0,0 +1,112 @@
+//! Error module
+//! Errors that may occur while building a model, from reading its file to checking its rules
+use crate::derivation_tree::Origin;
+use crate::parser::ParserError;
+use std::fmt::{Display, Formatter};
+
+/// Reasons for a model to be rejected
+#[derive(Debug)]
+pub enum SnifferError {
+    Io(std::io::Error),
+    /// A syntax error, located at the line and column of the unexpected token
+    Parse {
+        file: Option<String>,
+        line: usize,
+        column: usize,
+        error: ParserError,
+    },
+    /// Rules that are well formed but inconsistent with one another
+    Semantic(Vec<SemanticError>),
+}
+impl Display for SnifferError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnifferError::Io(e) => write!(f, "{e}"),
+            SnifferError::Parse {
+                file: Some(file),
+                line,
+                column,
+                error,
+            } => write!(f, "{file}:{line}:{column}: {error}"),
+            SnifferError::Parse {
+                file: None,
+                line,
+                column,
+                error,
+            } => write!(f, "{line}:{column}: {error}"),
+            SnifferError::Semantic(errors) => {
+                let mut errors = errors.iter();
+                if let Some(e) = errors.next() {
+                    write!(f, "{e}")?;
+                }
+                errors.try_for_each(|e| write!(f, "\n{e}"))
+            }
+        }
+    }
+}
+impl std::error::Error for SnifferError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SnifferError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+impl From<std::io::Error> for SnifferError {
+    fn from(value: std::io::Error) -> Self {
+        SnifferError::Io(value)
+    }
+}
+
+/// An inconsistency between rules, along with the rule where it was found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SemanticError {
+    /// A symbol used with another number of arguments than the first time it appeared
+    ArityMismatch {
+        symbol: String,
+        expected: usize,
+        found: usize,
+        origin: Origin,
+    },
+    /// A term whose sort is not the one its position expects
+    SortMismatch {
+        term: String,
+        expected: String,
+        found: String,
+        origin: Origin,
+    },
+    /// A sort used in a declaration without having been declared
+    UnknownSort { sort: String, origin: Origin },
+    /// A sort or function symbol declared twice
+    Redeclared { symbol: String, origin: Origin },
+}
+impl Display for SemanticError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SemanticError::ArityMismatch {
+                symbol,
+                expected,
+                found,
+                origin,
+            } => write!(
+                f,
+                "`{symbol}` expects {expected} argument(s) but is given {found} ({origin})"
+            ),
+            SemanticError::SortMismatch {
+                term,
+                expected,
+                found,
+                origin,
+            } => write!(
+                f,
+                "`{term}` is of sort `{found}` but `{expected}` is expected ({origin})"
+            ),
+            SemanticError::UnknownSort { sort, origin } => {
+                write!(f, "unknown sort `{sort}` ({origin})")
+            }
+            SemanticError::Redeclared { symbol, origin } => {
+                write!(f, "`{symbol}` is already declared ({origin})")
+            }
+        }
+    }
+}
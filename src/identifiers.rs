@@ -1,6 +1,9 @@
-use crate::ast::{Atom, InnerAtom, InnerRule, InnerTerm, Rule, Term};
+use crate::ast::{
+    Atom, Disequality, InnerAtom, InnerDisequality, InnerRule, InnerTerm, Rule, Term,
+};
 use rustc_hash::FxHashMap;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 /// Inner representation for identifiers
 #[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Copy, Clone, Debug)]
@@ -8,12 +11,33 @@ pub enum Identifier {
     Function(usize),
     Variable(usize),
 }
+
+/// Allocates fresh variables, which may be done from several threads at once
+#[derive(Default, Debug)]
+pub struct VariableAllocator {
+    count: AtomicUsize,
+}
+impl VariableAllocator {
+    pub fn fresh(&self) -> Identifier {
+        Identifier::Variable(self.count.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Returns an allocator whose variables are fresh with respect to those allocated so far,
+    /// leaving this one untouched, for terms which are only shown
+    pub fn scratch(&self) -> VariableAllocator {
+        VariableAllocator {
+            count: AtomicUsize::new(self.count.load(Ordering::Relaxed)),
+        }
+    }
+}
+
 #[derive(Default, Debug)]
 pub struct IdentifierServer {
-    variables_count: usize,
+    variables: VariableAllocator,
     functions_count: usize,
     ids_map: FxHashMap<Identifier, String>,
     names_map: FxHashMap<String, Identifier>,
+    arities: FxHashMap<Identifier, usize>,
 }
 impl IdentifierServer {
     /// Registers a new term, returning its identifier
@@ -29,14 +53,14 @@ impl IdentifierServer {
         }
     }
 
-    pub fn register_variable(&mut self) -> Identifier {
-        let id = self.variables_count;
-        let identifier = Identifier::Variable(id);
-        let symbol = String::from("VAR") + &id.to_string();
-        self.ids_map.insert(identifier, symbol.clone());
-        self.names_map.insert(symbol, identifier);
-        self.variables_count += 1;
-        identifier
+    /// Registers a new variable, named after its identifier
+    pub fn register_variable(&self) -> Identifier {
+        self.variables.fresh()
+    }
+
+    /// Returns the allocator of variables, to make rules fresh without a mutable access to the server
+    pub fn variables(&self) -> &VariableAllocator {
+        &self.variables
     }
 
     /// Returns the name associated with the given identifier
@@ -50,6 +74,16 @@ impl IdentifierServer {
     pub fn id_of(&self, name: &str) -> Option<&Identifier> {
         self.names_map.get(name)
     }
+
+    /// Records the number of arguments a function symbol takes
+    pub fn register_arity(&mut self, id: Identifier, arity: usize) {
+        self.arities.entry(id).or_insert(arity);
+    }
+
+    /// Returns the number of arguments of a function symbol, if it appeared in the model
+    pub fn arity_of(&self, id: &Identifier) -> Option<usize> {
+        self.arities.get(id).copied()
+    }
 }
 
 impl Term<String> {
@@ -73,7 +107,6 @@ impl Term<String> {
                 symbol: id_server.register_function(symbol),
                 parameters: parameters
                     .iter()
-                    .cloned()
                     .map(|t| t.to_inner(id_server, bindings))
                     .collect(),
             },
@@ -88,18 +121,14 @@ impl InnerTerm {
             },
             Term::Function { symbol, parameters } => Term::Function {
                 symbol: id_server.name_of(symbol).unwrap(),
-                parameters: parameters
-                    .iter()
-                    .cloned()
-                    .map(|t| t.to_string(id_server))
-                    .collect(),
+                parameters: parameters.iter().map(|t| t.to_string(id_server)).collect(),
             },
         }
     }
 
     pub fn make_fresh(
         &self,
-        id_server: &mut IdentifierServer,
+        variables: &VariableAllocator,
         bindings: &mut HashMap<Identifier, Identifier>,
     ) -> InnerTerm {
         match self {
@@ -107,7 +136,7 @@ impl InnerTerm {
                 let identifier = if let Some(identifier) = bindings.get(symbol) {
                     *identifier
                 } else {
-                    let identifier = id_server.register_variable();
+                    let identifier = variables.fresh();
                     bindings.insert(*symbol, identifier);
                     identifier
                 };
@@ -117,8 +146,7 @@ impl InnerTerm {
                 symbol: *symbol,
                 parameters: parameters
                     .iter()
-                    .cloned()
-                    .map(|t| t.make_fresh(id_server, bindings))
+                    .map(|t| t.make_fresh(variables, bindings))
                     .collect(),
             },
         }
@@ -155,7 +183,7 @@ impl InnerAtom {
 
     pub fn make_fresh(
         &self,
-        id_server: &mut IdentifierServer,
+        variables: &VariableAllocator,
         bindings: &mut HashMap<Identifier, Identifier>,
     ) -> InnerAtom {
         Atom {
@@ -163,12 +191,44 @@ impl InnerAtom {
             parameters: self
                 .parameters
                 .iter()
-                .map(|t| t.make_fresh(id_server, bindings))
+                .map(|t| t.make_fresh(variables, bindings))
                 .collect(),
         }
     }
 }
 
+impl Disequality<String> {
+    pub fn to_inner(
+        &self,
+        id_server: &mut IdentifierServer,
+        bindings: &mut HashMap<String, Identifier>,
+    ) -> InnerDisequality {
+        Disequality {
+            left: self.left.to_inner(id_server, bindings),
+            right: self.right.to_inner(id_server, bindings),
+        }
+    }
+}
+impl InnerDisequality {
+    pub fn to_string(&self, id_server: &IdentifierServer) -> Disequality<String> {
+        Disequality {
+            left: self.left.to_string(id_server),
+            right: self.right.to_string(id_server),
+        }
+    }
+
+    pub fn make_fresh(
+        &self,
+        variables: &VariableAllocator,
+        bindings: &mut HashMap<Identifier, Identifier>,
+    ) -> InnerDisequality {
+        Disequality {
+            left: self.left.make_fresh(variables, bindings),
+            right: self.right.make_fresh(variables, bindings),
+        }
+    }
+}
+
 impl Rule<String> {
     pub fn to_inner(&self, id_server: &mut IdentifierServer) -> InnerRule {
         let mut bindings = HashMap::new();
@@ -179,6 +239,11 @@ impl Rule<String> {
                 .iter()
                 .map(|a| a.to_inner(id_server, &mut bindings))
                 .collect(),
+            constraints: self
+                .constraints
+                .iter()
+                .map(|c| c.to_inner(id_server, &mut bindings))
+                .collect(),
         }
     }
 }
@@ -191,17 +256,33 @@ impl InnerRule {
                 .iter()
                 .map(|a| a.to_string(id_server))
                 .collect(),
+            constraints: self
+                .constraints
+                .iter()
+                .map(|c| c.to_string(id_server))
+                .collect(),
         }
     }
 
-    pub fn make_fresh(&self, id_server: &mut IdentifierServer) -> InnerRule {
+    /// Renames variables by order of first occurrence, so that rules which are equal up to
+    /// the naming of their variables have the same canonical form
+    pub fn canonical(&self) -> InnerRule {
+        self.make_fresh(&VariableAllocator::default())
+    }
+
+    pub fn make_fresh(&self, variables: &VariableAllocator) -> InnerRule {
         let mut bindings = HashMap::new();
         Rule {
-            conclusion: self.conclusion.make_fresh(id_server, &mut bindings),
+            conclusion: self.conclusion.make_fresh(variables, &mut bindings),
             premises: self
                 .premises
                 .iter()
-                .map(|a| a.make_fresh(id_server, &mut bindings))
+                .map(|a| a.make_fresh(variables, &mut bindings))
+                .collect(),
+            constraints: self
+                .constraints
+                .iter()
+                .map(|c| c.make_fresh(variables, &mut bindings))
                 .collect(),
         }
     }
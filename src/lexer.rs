@@ -9,16 +9,34 @@ use logos::Logos;
 pub enum Lexeme {
     #[token("=>")]
     Implies,
+    #[token("<>")]
+    Different,
     #[token(r"(")]
     OpeningParentheses,
     #[token(r")")]
     ClosingParentheses,
+    #[token("[")]
+    OpeningBracket,
+    #[token("]")]
+    ClosingBracket,
     #[token(",")]
     Comma,
     #[token("/\\")]
     And,
     #[token(".")]
     Stop,
+    #[token(":")]
+    Colon,
+    #[token("type")]
+    Type,
+    #[token("fun")]
+    Fun,
+    #[token("phase")]
+    Phase,
+    #[token("compromise")]
+    Compromise,
+    #[token("capabilities")]
+    Capabilities,
 
     #[regex(r"[A-Z][a-zA-Z0-9_']*", |lex| lex.slice().to_string())]
     Variable(String),
@@ -29,7 +47,7 @@ pub enum Lexeme {
 
     #[error]
     #[regex(r"[ \t\n\f]+", logos::skip)]
-    #[regex(r"#[^\n]*\n", logos::skip)]
-    UnrecognizedLexeme,
+    #[regex(r"#[^\n]*", logos::skip)]
+    Unrecognized,
 }
 logos_nom_bridge::token_parser!(token: Lexeme);
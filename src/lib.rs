@@ -1,28 +1,82 @@
+use crate::anti_unify::generalise_all;
 use crate::ast::*;
-use crate::derivation_tree::DerivationTree;
+pub use crate::ast::{Atom, Declaration, Disequality, Rule, Span, Term};
+use crate::attack::Attacker;
+pub use crate::attack::{AttackTrace, TraceStep};
+pub use crate::backward::Engine;
+pub use crate::bound::Bound;
+use crate::bound::Bounded;
+pub use crate::checker::{check, Certificate, CertificateStep, CheckError};
+pub use crate::correspondence::{
+    Correspondence, CorrespondenceResult, InjectivityViolation, Violation,
+};
+pub use crate::derivation_tree::{DerivationTree, Origin};
+pub use crate::error::{SemanticError, SnifferError};
 use crate::identifiers::{Identifier, IdentifierServer};
-pub use crate::parser::Parser;
-use crate::resolution::Selection;
+pub use crate::lint::{lint, Lint, Warning};
+pub use crate::parser::{Model, Parser, ParserError};
+use crate::resolution::ResolutionFailure;
+pub use crate::resolution::Selection;
+use crate::simplify::Simplifier;
+pub use crate::slice::{PruneReason, Slice};
+use crate::sorts::Signature;
+pub use crate::stats::Statistics;
+pub use crate::trace::{DiscardReason, SaturationEvent, Tracer, Verbosity};
+pub use crate::unify::UnificationFailure;
+pub use crate::why_not::WhyNot;
 use itertools::Itertools;
-use logos_nom_bridge::Tokens;
 use rustc_hash::{FxHashMap, FxHashSet};
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::Read;
 use std::path::Path;
+use std::str::FromStr;
+use std::time::Instant;
 
+mod anti_unify;
 mod ast;
+mod attack;
+mod backward;
+mod bound;
+mod checker;
+mod constraints;
+mod correspondence;
 mod derivation_tree;
+mod error;
 mod identifiers;
 mod lexer;
+mod lint;
 mod parser;
+mod phase;
 mod resolution;
+mod simplify;
+mod slice;
+mod sorts;
+mod stats;
+mod trace;
 mod unify;
 mod union_find;
+mod why_not;
 
 pub struct DerivationInfo {
     pub rules: (InnerRule, InnerRule),
     pub selected_atoms: (Selection<Identifier>, Selection<Identifier>),
+    /// Premises of the resolvent removed by the simplifications
+    pub removed: Vec<usize>,
+}
+impl DerivationInfo {
+    /// Derivation resolving premise `index` of `rule` with the conclusion of `other`
+    fn step(
+        rule: &InnerRule,
+        index: usize,
+        other: &InnerRule,
+        removed: Vec<usize>,
+    ) -> DerivationInfo {
+        let select = premise_selection(rule, index);
+        DerivationInfo {
+            rules: (rule.clone(), other.clone()),
+            selected_atoms: (select(rule), select(other)),
+            removed,
+        }
+    }
 }
 
 /// Sniffer's job is to saturate a set of rules, by deriving the current set until no
@@ -30,72 +84,567 @@ pub struct DerivationInfo {
 #[derive(Default)]
 pub struct Sniffer {
     pub rules: FxHashSet<InnerRule>,
-    derived_from: FxHashMap<InnerRule, DerivationInfo>,
+    // Rules of the input, along with where they were defined
+    input_rules: FxHashMap<InnerRule, Origin>,
+    // Derivations of every derived rule, keyed by its canonical form so that variants share them
+    derived_from: FxHashMap<InnerRule, Vec<DerivationInfo>>,
+    // Size of the smallest known derivation of every rule, computed lazily
+    derivation_sizes: FxHashMap<InnerRule, usize>,
+    // Steps of the last derivation found by the backward engine, kept apart from the rule set
+    // and keyed like `derived_from`
+    backward_steps: FxHashMap<InnerRule, DerivationInfo>,
+    // Declared sorts of function symbols
+    signature: Signature,
+    // File the model was loaded from, if any
+    file: Option<String>,
+    // Last phase of a phased model, if any
+    last_phase: Option<usize>,
+    // State of the saturations done so far, by selection strategy
+    saturations: FxHashMap<Strategy, SaturationState>,
+    // Statistics of the saturations done since they were last reset
+    stats: Statistics,
+    // Observer of the saturation events, if any
+    tracer: Option<Tracer>,
+    // Bound on the terms of resolvents, if any, and the rules approximated because of it
+    bound: Option<Bound>,
+    approximated: FxHashSet<InnerRule>,
+    // Whether resolution is spread over worker threads
+    #[cfg(feature = "parallel")]
+    parallel: bool,
 
     id_server: IdentifierServer,
 }
 impl Sniffer {
     /// Creates a Sniffer context from a `.pif` file
-    pub fn new<P: AsRef<Path>>(file: P) -> Result<Sniffer, ()> {
-        // Parses the `.pif` file
-        let mut file_contents = String::new();
-        if let Ok(mut file) = File::open(file) {
-            file.read_to_string(&mut file_contents).unwrap();
-        } else {
-            return Err(());
-        }
-        let parsed_rules =
-            Parser::parse_rules(Tokens::new(&file_contents)).expect("failed to parse file");
+    pub fn new<P: AsRef<Path>>(file: P) -> Result<Sniffer, SnifferError> {
+        let file_name = file.as_ref().display().to_string();
+        let source = std::fs::read_to_string(file)?;
+        Sniffer::from_source(&source, Some(file_name))
+    }
 
-        // Then maps every string id to an inner identifier
+    /// Creates a Sniffer context from rules built programmatically
+    pub fn from_rules(rules: Vec<Rule<String>>) -> Result<Sniffer, SnifferError> {
         let mut sniffer = Sniffer::default();
-        for rule in parsed_rules {
-            let inner_rule = rule.to_inner(&mut sniffer.id_server);
-            sniffer.rules.insert(inner_rule);
-        }
+        sniffer.add_rules(rules)?;
         Ok(sniffer)
     }
 
-    /// Returns a derivation that results in a given rule if one exists
-    pub fn find(&mut self, atom: &Atom<String>) -> Result<DerivationTree, SaturationFailure> {
-        let inner_atom = atom.to_inner(&mut self.id_server, &mut HashMap::new());
-        let inner_rule = Rule {
-            conclusion: inner_atom.clone(),
-            premises: vec![],
+    fn from_source(source: &str, file: Option<String>) -> Result<Sniffer, SnifferError> {
+        let model = Parser::parse_model(source).map_err(|error| {
+            let (line, column) = error.span.line_col(source);
+            SnifferError::Parse {
+                file: file.clone(),
+                line,
+                column,
+                error,
+            }
+        })?;
+        let origin = |span: Span| Origin::Input {
+            file: file.clone(),
+            line: span.line_col(source).0,
+        };
+
+        // Declarations apply to the whole file, wherever they appear
+        let mut sniffer = Sniffer {
+            file: file.clone(),
+            last_phase: model.last_phase,
+            ..Default::default()
         };
+        sniffer.add_located_declarations(
+            model
+                .declarations
+                .into_iter()
+                .map(|(declaration, span)| (declaration, origin(span)))
+                .collect(),
+        )?;
+        sniffer.add_located_rules(
+            model
+                .rules
+                .into_iter()
+                .map(|(rule, span)| (rule, origin(span)))
+                .collect(),
+        )?;
+        Ok(sniffer)
+    }
+
+    /// Spreads the resolutions of each saturation step over worker threads, which yields the
+    /// same saturated rules as sequential saturation, up to the naming of their variables
+    #[cfg(feature = "parallel")]
+    pub fn set_parallel(&mut self, parallel: bool) {
+        self.parallel = parallel
+    }
+
+    /// Returns the statistics of the saturations done since they were last reset
+    pub fn statistics(&self) -> &Statistics {
+        &self.stats
+    }
+
+    pub fn reset_statistics(&mut self) {
+        self.stats = Statistics::default()
+    }
+
+    pub fn bound(&self) -> Option<Bound> {
+        self.bound
+    }
+
+    /// Bounds the terms of the resolvents of the following saturations, which start over
+    pub fn set_bound(&mut self, bound: Option<Bound>) {
+        self.bound = bound;
+        self.reset_derivations()
+    }
+
+    /// Forgets every derived rule, along with how it was derived, keeping only the input rules
+    fn reset_derivations(&mut self) {
+        self.rules = self.input_rules.keys().cloned().collect();
+        self.derived_from.clear();
+        self.derivation_sizes.clear();
+        self.backward_steps.clear();
+        self.approximated.clear();
+        self.saturations.clear();
+    }
+
+    /// Reports the events of the following saturations to `tracer`, or stops reporting them,
+    /// returning the previous tracer
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) -> Option<Tracer> {
+        std::mem::replace(&mut self.tracer, tracer)
+    }
+
+    /// Reports an event about `rule` to the tracer, only building it if the tracer accepts it
+    fn emit(
+        &mut self,
+        level: Verbosity,
+        rule: &InnerRule,
+        event: impl FnOnce(&IdentifierServer) -> SaturationEvent,
+    ) {
+        if let Some(tracer) = &mut self.tracer {
+            let predicate = self.id_server.name_of(&rule.conclusion.symbol).unwrap();
+            if tracer.accepts(level, &predicate) {
+                tracer.notify(&event(&self.id_server))
+            }
+        }
+    }
+
+    /// Returns the file the model was loaded from, if any
+    pub fn file(&self) -> Option<&str> {
+        self.file.as_deref()
+    }
+
+    /// Returns the last phase of the model, if it is phased
+    pub fn last_phase(&self) -> Option<usize> {
+        self.last_phase
+    }
+
+    /// Scopes an atom of the attacker's knowledge to the last phase of a phased model, which
+    /// is what queries not given a phase ask about, other atoms being left as is
+    pub fn in_last_phase(&self, atom: &Atom<String>) -> Atom<String> {
+        match self.last_phase {
+            Some(n) => atom.at_phase(n),
+            None => atom.clone(),
+        }
+    }
+
+    /// Adds sort and function declarations to the model, against which the rules added afterwards are checked
+    pub fn add_declarations(&mut self, declarations: Vec<Declaration>) -> Result<(), SnifferError> {
+        self.add_located_declarations(
+            declarations
+                .into_iter()
+                .map(|d| (d, Origin::Added))
+                .collect(),
+        )
+    }
+
+    /// Adds declarations to the model, none of them being added if one is invalid
+    fn add_located_declarations(
+        &mut self,
+        declarations: Vec<(Declaration, Origin)>,
+    ) -> Result<(), SnifferError> {
+        let mut signature = self.signature.clone();
+        let mut arities = FxHashMap::default();
+        let mut errors = vec![];
+        for (declaration, origin) in declarations {
+            errors.extend(signature.declare(&declaration, &origin));
 
-        // Create a selection function using the query
-        let select = move |r: &InnerRule| {
-            for (i, p) in r.premises.iter().enumerate() {
-                if p.is_symbol(inner_atom.symbol) && !p.is_smth_of_variable() {
-                    return Selection::Premise(p.clone(), i);
+            // Declared functions fix the arity of their symbol
+            if let Declaration::Function {
+                symbol, parameters, ..
+            } = declaration
+            {
+                let known = self
+                    .id_server
+                    .id_of(&symbol)
+                    .and_then(|id| self.id_server.arity_of(id));
+                let expected =
+                    known.unwrap_or(*arities.entry(symbol.clone()).or_insert(parameters.len()));
+                if expected != parameters.len() {
+                    errors.push(SemanticError::ArityMismatch {
+                        symbol,
+                        expected,
+                        found: parameters.len(),
+                        origin,
+                    })
                 }
             }
-            Selection::Conclusion(r.conclusion.clone())
-        };
+        }
+        if !errors.is_empty() {
+            return Err(SnifferError::Semantic(errors));
+        }
+
+        self.signature = signature;
+        for (symbol, arity) in arities {
+            let id = self.id_server.register_function(&symbol);
+            self.id_server.register_arity(id, arity)
+        }
+        Ok(())
+    }
+
+    /// Adds a rule to the model
+    pub fn add_rule(&mut self, rule: Rule<String>) -> Result<(), SnifferError> {
+        self.add_rules(vec![rule])
+    }
+
+    /// Adds rules to the model, none of them being added if one is inconsistent with the others
+    pub fn add_rules(&mut self, rules: Vec<Rule<String>>) -> Result<(), SnifferError> {
+        self.add_located_rules(rules.into_iter().map(|r| (r, Origin::Added)).collect())
+    }
 
-        // Filter for not useful atoms
-        let keep = move |a: &Atom<Identifier>, c: &Atom<Identifier>| {
-            if a.symbol == inner_atom.symbol && a.is_smth_of_variable() {
-                c.contains_variable(&a.parameters[0])
-            } else {
-                true
+    fn add_located_rules(
+        &mut self,
+        rules: Vec<(Rule<String>, Origin)>,
+    ) -> Result<(), SnifferError> {
+        let mut errors = self.check_arities(&rules);
+        for (rule, origin) in &rules {
+            errors.extend(self.signature.check_rule(rule, origin))
+        }
+        if !errors.is_empty() {
+            return Err(SnifferError::Semantic(errors));
+        }
+
+        // Maps every string id to an inner identifier
+        for (rule, origin) in rules {
+            let inner_rule = rule.to_inner(&mut self.id_server);
+            for (symbol, arity) in inner_rule.symbols() {
+                self.id_server.register_arity(*symbol, arity)
+            }
+            if self.input_rules.contains_key(&inner_rule) {
+                continue;
             }
+            // New rules only need to be resolved against already saturated ones
+            for state in self.saturations.values_mut() {
+                state.inputs.insert(inner_rule.clone());
+                state.unprocessed.push(inner_rule.clone())
+            }
+            self.input_rules.insert(inner_rule.clone(), origin);
+            self.rules.insert(inner_rule);
+        }
+        Ok(())
+    }
+
+    /// Checks that every symbol is always given the same number of arguments, in the
+    /// given rules as well as in the ones already in the model
+    fn check_arities(&self, rules: &[(Rule<String>, Origin)]) -> Vec<SemanticError> {
+        let mut arities = FxHashMap::default();
+        let mut errors = vec![];
+        for (rule, origin) in rules {
+            for (symbol, found) in rule.symbols() {
+                let known = self
+                    .id_server
+                    .id_of(symbol)
+                    .and_then(|id| self.id_server.arity_of(id));
+                let expected = known.unwrap_or(*arities.entry(symbol).or_insert(found));
+                if expected != found {
+                    errors.push(SemanticError::ArityMismatch {
+                        symbol: symbol.clone(),
+                        expected,
+                        found,
+                        origin: origin.clone(),
+                    })
+                }
+            }
+        }
+        errors
+    }
+
+    /// Returns a derivation that results in a given rule if one exists. Queries about other
+    /// predicates than the attacker's knowledge, such as events, are looked up in the saturated
+    /// set, as are ground queries once the rule set was saturated through `saturate`
+    pub fn find(&mut self, atom: &Atom<String>) -> Result<DerivationTree, SaturationFailure> {
+        let inner_atom = atom.to_inner(&mut self.id_server, &mut HashMap::new());
+        // The selection of a query only resolves premises with its own predicate, which would
+        // leave the attacker's knowledge underived in the premises of events
+        let attacker = Attacker::new(&mut self.id_server);
+        let strategy = self.strategy_of(&inner_atom, attacker);
+        if strategy == Strategy::Global {
+            self.saturate();
+            return self
+                .lookup(&inner_atom)
+                .ok_or_else(|| self.failure(Strategy::Global));
+        }
+
+        let inner_rule = Rule {
+            conclusion: inner_atom.clone(),
+            premises: vec![],
+            constraints: vec![],
         };
 
-        // We keep saturating our rule set until we either find our atom or the set is fully saturated
-        self.saturate(&inner_rule, select, keep);
+        // We keep saturating our rule set until we either find our atom or the set is fully saturated,
+        // resuming from the previous saturation under the same selection
+        if !self.rules.contains(&inner_rule) {
+            let mut state = self.saturation_state(strategy);
+            self.add_slice(&mut state, &inner_atom);
+            self.run_saturation(
+                &mut state,
+                Some(&inner_rule),
+                query_selection(inner_atom.symbol, attacker),
+            );
+            self.saturations.insert(strategy, state);
+        }
 
         if self.rules.contains(&inner_rule) {
-            Ok(self
+            return Ok(self
                 .derivation_tree(&Rule {
                     conclusion: atom.clone(),
                     premises: vec![],
+                    constraints: vec![],
                 })
-                .unwrap())
+                .unwrap());
+        }
+
+        // Ground queries may be instances of a derived fact, such as an approximated one, or of
+        // a solved rule whose premises are derivable, such as `att(X) => att(na[X])`
+        if inner_atom.is_ground() {
+            let select = query_selection(inner_atom.symbol, attacker);
+            let solved = self.saturations[&strategy]
+                .processed
+                .iter()
+                .filter(|r| matches!(select(r), Selection::Conclusion(_)))
+                .sorted_by_key(|r| r.premises.len())
+                .cloned()
+                .collect_vec();
+            let fact = self.derive_fact(
+                &inner_atom,
+                &solved,
+                attacker,
+                &mut FxHashSet::default(),
+                &mut FxHashMap::default(),
+            );
+            if let Some(fact) = fact {
+                let root = fact.to_string(&self.id_server);
+                return Ok(self.derivation_trees_of(&fact, &root, 1).pop().unwrap());
+            }
+        }
+        Err(self.failure(strategy))
+    }
+
+    /// Strategy under which queries of `atom` are answered: atoms of other predicates than the
+    /// attacker's, and ground ones once the rule set is saturated globally, are looked up
+    fn strategy_of(&self, atom: &InnerAtom, attacker: Attacker) -> Strategy {
+        if !attacker.is_predicate(atom.symbol)
+            || (self.saturations.contains_key(&Strategy::Global) && atom.is_ground())
+        {
+            Strategy::Global
         } else {
-            Err(SaturationFailure::Saturated)
+            Strategy::Query(atom.symbol)
+        }
+    }
+
+    /// Failure of a query whose saturation under `strategy` is over
+    fn failure(&self, strategy: Strategy) -> SaturationFailure {
+        if self.saturations.get(&strategy).is_some_and(|s| s.rejected) {
+            SaturationFailure::Incomplete
+        } else {
+            SaturationFailure::Saturated
+        }
+    }
+
+    /// Saturates the rule set under a selection function independent of any query, so that
+    /// queries are then answered by looking the saturated set up. Rules added afterwards are
+    /// saturated on the next query. Returns the number of rules of the saturated set
+    pub fn saturate(&mut self) -> usize {
+        let attacker = Attacker::new(&mut self.id_server);
+        let mut state = self.saturation_state(Strategy::Global);
+        self.run_saturation(&mut state, None, global_selection(attacker));
+        let size = state.processed.len();
+        self.saturations.insert(Strategy::Global, state);
+        size
+    }
+
+    /// Takes the state of the saturation under `strategy` out, or starts it from the input rules.
+    /// Queries only start from the input rules of their slice, added by `add_slice`
+    fn saturation_state(&mut self, strategy: Strategy) -> SaturationState {
+        self.saturations.remove(&strategy).unwrap_or_else(|| {
+            let inputs: FxHashSet<_> = match strategy {
+                Strategy::Query(_) => FxHashSet::default(),
+                Strategy::Global => self.input_rules.keys().cloned().collect(),
+            };
+            SaturationState {
+                processed: FxHashSet::default(),
+                unprocessed: inputs.iter().cloned().collect(),
+                variants: FxHashSet::default(),
+                rejected: false,
+                inputs,
+            }
+        })
+    }
+
+    /// Adds the input rules of the slice of `query` the saturation does not start from yet
+    fn add_slice(&self, state: &mut SaturationState, query: &InnerAtom) {
+        for rule in self.sliced_inputs(query) {
+            if state.inputs.insert(rule.clone()) {
+                state.unprocessed.push(rule)
+            }
+        }
+    }
+
+    /// Looks a ground atom up in the global saturation, returning the derivation of a fact
+    /// it is an instance of
+    fn lookup(&mut self, atom: &InnerAtom) -> Option<DerivationTree> {
+        let start = Instant::now();
+        let attacker = Attacker::new(&mut self.id_server);
+        let select = global_selection(attacker);
+        // Once saturated, every derivable atom is derived by rules whose conclusion is selected
+        let solved = self.saturations[&Strategy::Global]
+            .processed
+            .iter()
+            .filter(|r| matches!(select(r), Selection::Conclusion(_)))
+            .sorted_by_key(|r| r.premises.len())
+            .cloned()
+            .collect_vec();
+
+        let fact = self.derive_fact(
+            atom,
+            &solved,
+            attacker,
+            &mut FxHashSet::default(),
+            &mut FxHashMap::default(),
+        );
+        self.stats.lookup_time += start.elapsed();
+
+        let fact = fact?;
+        let root = fact.to_string(&self.id_server);
+        self.derivation_trees_of(&fact, &root, 1).pop()
+    }
+
+    /// Derives a fact `atom` is an instance of, by resolving the premises of a solved rule
+    /// concluding it with facts derived the same way. Since solved rules only have premises of
+    /// the form `att(X)`, the atoms to derive are subterms of `atom`, and `proving` guards
+    /// against those depending on one another. Non ground atoms are only matched against facts.
+    /// The constraints of the fact must still hold once instantiated to `atom`
+    fn derive_fact(
+        &mut self,
+        atom: &InnerAtom,
+        solved: &[InnerRule],
+        attacker: Attacker,
+        proving: &mut FxHashSet<InnerAtom>,
+        proved: &mut FxHashMap<InnerAtom, InnerRule>,
+    ) -> Option<InnerRule> {
+        if let Some(fact) = proved.get(atom) {
+            return Some(fact.clone());
+        }
+        let concludes = |rule: &InnerRule| {
+            atom.unify(&rule.conclusion).is_some_and(|bindings| {
+                rule.apply_fully(&bindings).simplify_constraints().is_some()
+            })
+        };
+        if !atom.is_ground() {
+            return solved
+                .iter()
+                .find(|r| r.premises.is_empty() && concludes(r))
+                .cloned();
+        }
+        if !proving.insert(atom.clone()) {
+            return None;
+        }
+
+        let mut derived = None;
+        'rules: for rule in solved {
+            let mut current = rule.clone();
+            while let Some(premise) = current.premises.first() {
+                let Some(bindings) = atom.unify(&current.conclusion) else {
+                    continue 'rules;
+                };
+                let instance = premise.apply_fully(&bindings);
+                let Some(fact) = self.derive_fact(&instance, solved, attacker, proving, proved)
+                else {
+                    continue 'rules;
+                };
+                match self.resolve_with_fact(&current, &fact, attacker) {
+                    Some(resolvent) => current = resolvent,
+                    None => continue 'rules,
+                }
+            }
+            if concludes(&current) {
+                derived = Some(current);
+                break;
+            }
+        }
+
+        proving.remove(atom);
+        if let Some(fact) = &derived {
+            proved.insert(atom.clone(), fact.clone());
+        }
+        derived
+    }
+
+    /// Resolves the first premise of `rule` with `fact`, recording the step so that the
+    /// resolvent can be explained like any other derived rule
+    fn resolve_with_fact(
+        &mut self,
+        rule: &InnerRule,
+        fact: &InnerRule,
+        attacker: Attacker,
+    ) -> Option<InnerRule> {
+        let resolvent = rule.try_resolve(fact, premise_selection(rule, 0));
+        self.stats.record_lookup_resolution(&resolvent);
+        let (resolvent, removed) = self.simplify_resolvent(resolvent.ok()?, attacker)?;
+        self.record_step(&resolvent, rule, 0, fact, removed);
+        Some(resolvent)
+    }
+
+    /// Resolves premise `index` of `rule` with the conclusion of `other`, returning the
+    /// simplified resolvent made fresh, along with the premises the simplifications removed
+    fn resolve_premise(
+        &mut self,
+        rule: &InnerRule,
+        index: usize,
+        other: &InnerRule,
+        attacker: Attacker,
+    ) -> Option<(InnerRule, Vec<usize>)> {
+        let resolvent = rule.try_resolve(other, premise_selection(rule, index));
+        self.stats.record_resolution(&resolvent);
+        self.simplify_resolvent(resolvent.ok()?, attacker)
+    }
+
+    /// Simplifies `resolvent` and makes it fresh, along with the premises the simplifications
+    /// removed, or returns `None` if it is a tautology
+    fn simplify_resolvent(
+        &self,
+        resolvent: InnerRule,
+        attacker: Attacker,
+    ) -> Option<(InnerRule, Vec<usize>)> {
+        let (resolvent, removed) = Simplifier::new(attacker).simplify(resolvent)?;
+        Some((resolvent.make_fresh(self.id_server.variables()), removed))
+    }
+
+    /// Records that `resolvent` results from resolving premise `index` of `rule` with the
+    /// conclusion of `other`, the simplifications removing premises `removed`
+    fn record_step(
+        &mut self,
+        resolvent: &InnerRule,
+        rule: &InnerRule,
+        index: usize,
+        other: &InnerRule,
+        removed: Vec<usize>,
+    ) {
+        let derivations = self.derived_from.entry(resolvent.canonical()).or_default();
+        if !derivations
+            .iter()
+            .any(|d| &d.rules.0 == rule && &d.rules.1 == other)
+        {
+            derivations.push(DerivationInfo::step(rule, index, other, removed));
+            self.derivation_sizes.clear();
         }
+        self.emit_resolved(resolvent, rule, other);
+        self.rules.insert(resolvent.clone());
     }
 
     /// We derive new rules through resolution:
@@ -118,91 +667,435 @@ impl Sniffer {
     ///     add C to E_2
     /// return E_2
     ///
-    /// return None if it is finis hed because it means that we doesn't have find our solution
-    /// return Some(DerivationTree ??) if it is finished because we have find our solution
-    fn saturate(
+    /// E_1 and E_2 are the unprocessed and processed rules of `state`, which is kept between
+    /// calls, so that saturation resumes where it stopped. Stops early once `searching` is processed
+    fn run_saturation(
         &mut self,
-        searching: &InnerRule,
-        select: impl Fn(&InnerRule) -> Selection<Identifier>,
-        keep: impl Fn(&Atom<Identifier>, &Atom<Identifier>) -> bool,
-    ) -> Option<DerivationTree> {
-        let mut rules_set: Vec<_> = self.rules.clone().into_iter().collect();
-
-        while let Some(rule) = rules_set.pop() {
-            for other in &self.rules {
-                if let Some(r) = rule
-                    .resolve(other, &select, &keep)
-                    .map(|r| r.make_fresh(&mut self.id_server))
-                {
-                    if !(r.premises.len() == 1 && r.premises[0] == r.conclusion) && r != rule {
-                        let selected = (select(&rule), select(other));
-                        self.derived_from
-                            .entry(r.clone())
-                            .or_insert_with(|| DerivationInfo {
-                                rules: (rule.clone(), other.clone()),
-                                selected_atoms: selected,
-                            });
-                        rules_set.push(r)
+        state: &mut SaturationState,
+        searching: Option<&InnerRule>,
+        select: impl Fn(&InnerRule) -> Selection<Identifier> + Sync,
+    ) {
+        let start = Instant::now();
+        let simplifier = Simplifier::new(Attacker::new(&mut self.id_server));
+        while let Some(rule) = state.unprocessed.pop() {
+            if !state.processed.insert(rule.clone()) {
+                continue;
+            }
+            state.variants.insert(rule.canonical());
+            self.emit(Verbosity::Selections, &rule, |ids| {
+                SaturationEvent::Selected {
+                    rule: rule.to_string(ids),
+                    selection: Selection::try_from((&select(&rule), ids)).unwrap(),
+                }
+            });
+
+            let resolution_start = Instant::now();
+            let resolvents = self.resolvents(&rule, &state.processed, &select);
+            self.stats.resolution_time += resolution_start.elapsed();
+
+            for (other, r) in resolvents {
+                self.stats.record_resolution(&r);
+                let Ok(r) = r else {
+                    continue;
+                };
+                self.stats.generated += 1;
+                if Simplifier::is_tautology(&r) {
+                    self.stats.tautologies += 1;
+                    self.emit_discarded(&r, DiscardReason::Tautology);
+                    continue;
+                }
+                let (r, removed) = simplifier.reduce(r);
+                let bounded = match self.bound {
+                    Some(bound) => bound.apply(r, self.id_server.variables()),
+                    None => Bounded::Kept(r),
+                };
+                let r = match bounded {
+                    Bounded::Kept(r) => r,
+                    Bounded::Approximated(r) => {
+                        self.stats.approximated += 1;
+                        self.approximated.insert(r.clone());
+                        r
                     }
+                    Bounded::Rejected(r) => {
+                        self.stats.rejected += 1;
+                        state.rejected = true;
+                        self.emit_discarded(&r, DiscardReason::OutOfBound);
+                        continue;
+                    }
+                };
+                if r == rule {
+                    self.stats.duplicates += 1;
+                    self.emit_discarded(&r, DiscardReason::Duplicate);
+                    continue;
+                }
+
+                let selected = (select(&rule), select(other));
+                let derivations = self.derived_from.entry(r.canonical()).or_default();
+                if !derivations
+                    .iter()
+                    .any(|d| d.rules.0 == rule && &d.rules.1 == other)
+                {
+                    derivations.push(DerivationInfo {
+                        rules: (rule.clone(), other.clone()),
+                        selected_atoms: selected,
+                        removed,
+                    });
+                    self.derivation_sizes.clear();
+                }
+                // Saturations deriving ever new non-ground rules, such as bounded or phased ones,
+                // only terminate once rules are compared up to renaming
+                let variant = !state.variants.insert(r.canonical());
+                if variant || state.processed.contains(&r) {
+                    self.stats.duplicates += 1;
+                    self.emit_discarded(&r, DiscardReason::Duplicate);
+                } else {
+                    let predicate = self.id_server.name_of(&r.conclusion.symbol).unwrap();
+                    self.stats.record_kept(&r, predicate);
+                    self.emit_resolved(&r, &rule, other);
+                    state.unprocessed.push(r)
                 }
             }
 
             self.rules.insert(rule.clone());
-            if &rule == searching {
-                return None;
+            if searching == Some(&rule) {
+                break;
             }
         }
+        self.stats.saturation_time += start.elapsed();
+    }
 
-        None
+    fn emit_resolved(&mut self, resolvent: &InnerRule, rule: &InnerRule, other: &InnerRule) {
+        self.emit(Verbosity::Resolvents, resolvent, |ids| {
+            SaturationEvent::Resolved {
+                rule: resolvent.to_string(ids),
+                parents: (rule.to_string(ids), other.to_string(ids)),
+            }
+        })
+    }
+
+    fn emit_discarded(&mut self, resolvent: &InnerRule, reason: DiscardReason) {
+        self.emit(Verbosity::Discarded, resolvent, |ids| {
+            SaturationEvent::Discarded {
+                rule: resolvent.to_string(ids),
+                reason,
+            }
+        })
     }
 
-    /// Returns the derivation tree for a given rule
+    /// Resolves `rule` against every processed rule, each resolvent being made fresh. In parallel
+    /// mode, resolutions are spread over worker threads, resolvents being kept in the same order
+    fn resolvents<'a>(
+        &self,
+        rule: &InnerRule,
+        processed: &'a FxHashSet<InnerRule>,
+        select: &(impl Fn(&InnerRule) -> Selection<Identifier> + Sync),
+    ) -> Vec<(&'a InnerRule, Result<InnerRule, ResolutionFailure>)> {
+        let variables = self.id_server.variables();
+        let resolve = |other: &'a InnerRule| {
+            let resolvent = rule.try_resolve(other, select);
+            (other, resolvent.map(|r| r.make_fresh(variables)))
+        };
+
+        #[cfg(feature = "parallel")]
+        if self.parallel {
+            use rayon::prelude::*;
+            return processed.par_iter().map(resolve).collect();
+        }
+        processed.iter().map(resolve).collect()
+    }
+
+    /// Returns the smallest derivation tree for a given rule
     pub fn derivation_tree(&mut self, root: &Rule<String>) -> Option<DerivationTree> {
-        fn inner(root: &InnerRule, sniffer: &Sniffer) -> Option<DerivationTree> {
-            let mut derivation_tree = DerivationTree::new(root.to_string(&sniffer.id_server));
-            if let Some(DerivationInfo {
-                rules,
-                selected_atoms,
-            }) = sniffer.derived_from.get(root)
-            {
-                if let Some(mut tree) = inner(&rules.0, sniffer) {
-                    tree.set_selection(
-                        Selection::try_from((&selected_atoms.0, &sniffer.id_server)).unwrap(),
-                    );
-                    derivation_tree.add_subtree(tree)
-                }
-                if let Some(mut tree) = inner(&rules.1, sniffer) {
-                    tree.set_selection(
-                        Selection::try_from((&selected_atoms.1, &sniffer.id_server)).unwrap(),
-                    );
-                    derivation_tree.add_subtree(tree)
+        self.derivation_trees(root, 1).pop()
+    }
+
+    /// Returns up to `count` alternative derivation trees for a given rule, smallest first.
+    /// Alternatives differ by their last resolution step, their subtrees being the smallest
+    /// derivations of the resolved rules
+    pub fn derivation_trees(&mut self, root: &Rule<String>, count: usize) -> Vec<DerivationTree> {
+        let inner_rule = root.to_inner(&mut self.id_server);
+        self.derivation_trees_of(&inner_rule, root, count)
+    }
+
+    fn derivation_trees_of(
+        &mut self,
+        inner_rule: &InnerRule,
+        root: &Rule<String>,
+        count: usize,
+    ) -> Vec<DerivationTree> {
+        let start = Instant::now();
+        let trees = self.build_derivation_trees(inner_rule, root, count);
+        self.stats.derivation_time += start.elapsed();
+        trees
+    }
+
+    fn build_derivation_trees(
+        &mut self,
+        inner_rule: &InnerRule,
+        root: &Rule<String>,
+        count: usize,
+    ) -> Vec<DerivationTree> {
+        self.compute_derivation_sizes();
+
+        let mut derivations = self
+            .derived_from
+            .get(&inner_rule.canonical())
+            .into_iter()
+            .flatten()
+            .filter(|_| !self.input_rules.contains_key(inner_rule))
+            .filter_map(|d| self.size_of(d).map(|size| (size, d)))
+            .collect_vec();
+        if derivations.is_empty() {
+            let mut tree = self.new_tree(inner_rule, root.clone());
+            if let Some(origin) = self.input_rules.get(inner_rule) {
+                tree.set_origin(origin.clone())
+            }
+            return vec![tree];
+        }
+        derivations.sort_by_key(|(size, _)| *size);
+
+        // Sub-derivations are shared between alternatives
+        let mut builder = DerivationBuilder::default();
+        derivations
+            .into_iter()
+            .take(count)
+            .map(|(_, d)| {
+                let mut tree = self.new_tree(inner_rule, root.clone());
+                builder.in_progress.insert(inner_rule.canonical());
+                self.add_smallest_subtrees(&mut tree, d, &mut builder);
+                builder.in_progress.remove(&inner_rule.canonical());
+                tree
+            })
+            .collect()
+    }
+
+    /// Creates a derivation tree node for `rule`, flagged if it was approximated
+    fn new_tree(&self, inner_rule: &InnerRule, rule: Rule<String>) -> DerivationTree {
+        let mut tree = DerivationTree::new(rule);
+        if self.approximated.contains(inner_rule) {
+            tree.set_approximated()
+        }
+        tree
+    }
+
+    /// Computes the size of the smallest derivation of every rule, up to renaming, input rules
+    /// being leaves. As derivations may reference each other, sizes are refined until reaching
+    /// a fixpoint
+    fn compute_derivation_sizes(&mut self) {
+        if !self.derivation_sizes.is_empty() {
+            return;
+        }
+
+        let mut sizes: FxHashMap<InnerRule, usize> = self
+            .input_rules
+            .keys()
+            .map(|r| (r.canonical(), 1))
+            .collect();
+        let parents = self
+            .derived_from
+            .iter()
+            .filter(|(rule, _)| sizes.get(*rule) != Some(&1))
+            .map(|(rule, derivations)| {
+                let parents = derivations
+                    .iter()
+                    .map(|d| (d.rules.0.canonical(), d.rules.1.canonical()))
+                    .collect_vec();
+                (rule, parents)
+            })
+            .collect_vec();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (rule, derivations) in &parents {
+                let smallest = derivations
+                    .iter()
+                    .filter_map(|(r1, r2)| Some(1 + sizes.get(r1)? + sizes.get(r2)?))
+                    .min();
+                if let Some(size) = smallest {
+                    if sizes.get(*rule).is_none_or(|s| size < *s) {
+                        sizes.insert((*rule).clone(), size);
+                        changed = true;
+                    }
                 }
-            };
-            Some(derivation_tree)
+            }
         }
+        self.derivation_sizes = sizes;
+    }
 
-        let inner_rule = root.to_inner(&mut self.id_server);
+    /// Size of the derivation tree obtained through `derivation`, if both its premises are derivable
+    fn size_of(&self, derivation: &DerivationInfo) -> Option<usize> {
+        let (r1, r2) = &derivation.rules;
+        Some(
+            1 + self.derivation_sizes.get(&r1.canonical())?
+                + self.derivation_sizes.get(&r2.canonical())?,
+        )
+    }
 
-        let mut decision_tree = DerivationTree::new(root.clone());
-        if let Some(DerivationInfo {
+    fn smallest_derivation(&self, rule: &InnerRule) -> Option<&DerivationInfo> {
+        if self.input_rules.contains_key(rule) {
+            return None;
+        }
+        // Rules derived backward have a single derivation
+        if let Some(derivation) = self.backward_steps.get(&rule.canonical()) {
+            return Some(derivation);
+        }
+        self.derived_from
+            .get(&rule.canonical())?
+            .iter()
+            .filter_map(|d| self.size_of(d).map(|size| (size, d)))
+            .min_by_key(|(size, _)| *size)
+            .map(|(_, d)| d)
+    }
+
+    fn add_smallest_subtrees(
+        &self,
+        tree: &mut DerivationTree,
+        derivation: &DerivationInfo,
+        builder: &mut DerivationBuilder,
+    ) {
+        let DerivationInfo {
             rules,
             selected_atoms,
-        }) = self.derived_from.get(&inner_rule)
-        {
-            if let Some(mut tree) = inner(&rules.0, self) {
-                tree.set_selection(
-                    Selection::try_from((&selected_atoms.0, &self.id_server)).unwrap(),
-                );
-                decision_tree.add_subtree(tree)
-            }
-            if let Some(mut tree) = inner(&rules.1, self) {
-                tree.set_selection(
-                    Selection::try_from((&selected_atoms.1, &self.id_server)).unwrap(),
-                );
-                decision_tree.add_subtree(tree)
+            ..
+        } = derivation;
+        for (rule, selection) in [(&rules.0, &selected_atoms.0), (&rules.1, &selected_atoms.1)] {
+            let mut subtree = self.smallest_derivation_tree(rule, builder);
+            subtree.set_selection(Selection::try_from((selection, &self.id_server)).unwrap());
+            tree.add_subtree(subtree)
+        }
+    }
+
+    /// Builds the smallest derivation of a rule, as a DAG where a rule used many times is only
+    /// built once. Smallest derivations cannot loop, but derivations are guarded against cycles anyway
+    fn smallest_derivation_tree(
+        &self,
+        rule: &InnerRule,
+        builder: &mut DerivationBuilder,
+    ) -> DerivationTree {
+        let key = rule.canonical();
+        if let Some(tree) = builder.built.get(&key) {
+            return tree.clone();
+        }
+
+        let mut tree = self.new_tree(rule, rule.to_string(&self.id_server));
+        if !builder.in_progress.insert(key.clone()) {
+            tree.set_origin(Origin::Cycle);
+            return tree;
+        }
+        if let Some(origin) = self.input_rules.get(rule) {
+            tree.set_origin(origin.clone())
+        } else if let Some(derivation) = self.smallest_derivation(rule) {
+            self.add_smallest_subtrees(&mut tree, derivation, builder);
+        }
+        builder.in_progress.remove(&key);
+
+        builder.built.insert(key, tree.clone());
+        tree
+    }
+
+    /// Returns a proof certificate for the smallest derivation of a given rule, up to renaming,
+    /// if it is an input rule or was derived
+    pub fn certificate(&mut self, root: &Rule<String>) -> Option<Certificate> {
+        let inner_rule = root.to_inner(&mut self.id_server);
+        self.compute_derivation_sizes();
+
+        let mut steps = vec![];
+        self.add_certificate_steps(&inner_rule, &mut steps, &mut FxHashMap::default())?;
+        Some(Certificate { steps })
+    }
+
+    /// Adds the steps certifying `rule` after those of its parents, returning the index of its step
+    fn add_certificate_steps(
+        &self,
+        rule: &InnerRule,
+        steps: &mut Vec<CertificateStep>,
+        indices: &mut FxHashMap<InnerRule, usize>,
+    ) -> Option<usize> {
+        if let Some(i) = indices.get(rule) {
+            return Some(*i);
+        }
+
+        let step = if self.input_rules.contains_key(rule) {
+            CertificateStep::Input {
+                rule: rule.to_string(&self.id_server),
+            }
+        } else {
+            let DerivationInfo {
+                rules,
+                selected_atoms,
+                removed,
+            } = self.smallest_derivation(rule)?;
+            let (p, c, premise_index) = match selected_atoms {
+                (Selection::Premise(_, i), Selection::Conclusion(_)) => (&rules.0, &rules.1, *i),
+                (Selection::Conclusion(_), Selection::Premise(_, i)) => (&rules.1, &rules.0, *i),
+                _ => return None,
+            };
+            let premise_step = self.add_certificate_steps(p, steps, indices)?;
+            let conclusion_step = self.add_certificate_steps(c, steps, indices)?;
+
+            let bindings = p.premises[premise_index].unify(&c.conclusion)?;
+            CertificateStep::Resolution {
+                rule: rule.to_string(&self.id_server),
+                premise_step,
+                premise_index,
+                conclusion_step,
+                unifier: bindings
+                    .into_iter()
+                    .filter(|(t, u)| t.is_variable() && t != u)
+                    .map(|(t, u)| {
+                        (
+                            self.id_server.name_of(t.symbol()).unwrap(),
+                            u.to_string(&self.id_server),
+                        )
+                    })
+                    .collect(),
+                removed: removed.clone(),
             }
         };
-        Some(decision_tree)
+
+        steps.push(step);
+        indices.insert(rule.clone(), steps.len() - 1);
+        Some(steps.len() - 1)
+    }
+
+    /// Groups the derived rules by the shape of their conclusion (its symbol and the symbols
+    /// heading its parameters) and by their premises symbols, then returns the generalisation
+    /// of each group along with the number of rules it covers
+    pub fn generalisations(&self) -> Vec<(Rule<String>, usize)> {
+        fn head(t: &InnerTerm) -> Option<Identifier> {
+            match t {
+                Term::Function { symbol, .. } => Some(*symbol),
+                Term::Variable { .. } => None,
+            }
+        }
+
+        let mut groups: FxHashMap<_, Vec<&InnerRule>> = FxHashMap::default();
+        for rule in self
+            .rules
+            .iter()
+            .filter(|r| self.derived_from.contains_key(&r.canonical()))
+        {
+            let shape = (
+                rule.conclusion.symbol,
+                rule.conclusion.parameters.iter().map(head).collect_vec(),
+                rule.premises.iter().map(|p| p.symbol).collect_vec(),
+            );
+            groups.entry(shape).or_default().push(rule);
+        }
+
+        let variables = self.id_server.variables().scratch();
+        let mut generalisations = groups
+            .into_values()
+            .filter_map(|group| {
+                let count = group.len();
+                generalise_all(group.into_iter(), &variables)
+                    .map(|r| (r.to_string(&self.id_server), count))
+            })
+            .collect_vec();
+        generalisations.sort_by(|(r1, c1), (r2, c2)| {
+            c2.cmp(c1).then_with(|| r1.to_string().cmp(&r2.to_string()))
+        });
+        generalisations
     }
 
     pub fn rules_to_string(&self) -> String {
@@ -217,8 +1110,424 @@ impl Sniffer {
     }
 }
 
+/// Selection function of a query: the first premise with the query's predicate which is
+/// not of the form `Symbol(X)`, nor `att_phase(n, X)`, or the conclusion if there is none
+fn query_selection(
+    symbol: Identifier,
+    attacker: Attacker,
+) -> impl Fn(&InnerRule) -> Selection<Identifier> {
+    move |r: &InnerRule| {
+        for (i, p) in r.premises.iter().enumerate() {
+            if p.is_symbol(symbol) && !p.is_smth_of_variable() && !attacker.knows_variable(p) {
+                return Selection::Premise(p.clone(), i);
+            }
+        }
+        Selection::Conclusion(r.conclusion.clone())
+    }
+}
+
+/// Selection of premise `index` of `rule`, and of the conclusion of any other rule
+fn premise_selection(
+    rule: &InnerRule,
+    index: usize,
+) -> impl Fn(&InnerRule) -> Selection<Identifier> + '_ {
+    move |r: &InnerRule| {
+        if r == rule {
+            Selection::Premise(rule.premises[index].clone(), index)
+        } else {
+            Selection::Conclusion(r.conclusion.clone())
+        }
+    }
+}
+
+/// Selection function independent of any query: the first premise which is not of the form
+/// `att(X)`, nor `att_phase(n, X)`, or the conclusion if there is none
+fn global_selection(attacker: Attacker) -> impl Fn(&InnerRule) -> Selection<Identifier> {
+    move |r: &InnerRule| {
+        for (i, p) in r.premises.iter().enumerate() {
+            if !attacker.knows_variable(p) {
+                return Selection::Premise(p.clone(), i);
+            }
+        }
+        Selection::Conclusion(r.conclusion.clone())
+    }
+}
+
+/// Creates a Sniffer context from the contents of a `.pif` file
+impl FromStr for Sniffer {
+    type Err = SnifferError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Sniffer::from_source(source, None)
+    }
+}
+
+/// Selection strategies, each having its own saturation of the rule set
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Strategy {
+    /// Selection driven by the predicate of a query
+    Query(Identifier),
+    /// Selection independent of queries, whose saturation answers all of them
+    Global,
+}
+
+/// Saturation of the rule set under a selection function: processed rules have been resolved
+/// against each other, while unprocessed ones are yet to be resolved against them
+struct SaturationState {
+    processed: FxHashSet<InnerRule>,
+    unprocessed: Vec<InnerRule>,
+    // Canonical forms of the rules met
+    variants: FxHashSet<InnerRule>,
+    // Whether resolvents were discarded for exceeding the bound
+    rejected: bool,
+    // Input rules the saturation started from, or were added to it since
+    inputs: FxHashSet<InnerRule>,
+}
+
+/// Keeps track of the derivation trees built so far, to share them, and of those being built, to
+/// detect cycles, by the canonical form of their root
+#[derive(Default)]
+struct DerivationBuilder {
+    built: FxHashMap<InnerRule, DerivationTree>,
+    in_progress: FxHashSet<InnerRule>,
+}
+
 /// Represents the result of a saturation attempt
 pub enum SaturationFailure {
     Saturated,     // The saturation attempt did not create any new rule
     DerivedBottom, // The saturation derived a contradiction
+    Incomplete, // The saturation discarded resolvents exceeding the bound, which may have derived the atom
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constrained_premise_test() {
+        // Once resolved, att(Z) constrains Z along with att(h(Z)): dropping it would derive
+        // att(secret) from att(h(k)) without the attacker knowing k
+        let mut sniffer = Sniffer::from_str(
+            "att(Z) => att(pair(Z, c)). att(pair(X, Y)) /\\ att(h(X)) => att(secret). att(h(k)).",
+        )
+        .unwrap();
+        assert!(sniffer
+            .find(&Parser::parse_query("att(secret).").unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn arity_mismatch_test() {
+        let Err(SnifferError::Semantic(errors)) =
+            Sniffer::from_str("att(pair(X, Y)) => att(X).\natt(pair(a)).")
+        else {
+            panic!("Expected a semantic error")
+        };
+        assert_eq!(
+            errors,
+            vec![SemanticError::ArityMismatch {
+                symbol: "pair".to_string(),
+                expected: 2,
+                found: 1,
+                origin: Origin::Input {
+                    file: None,
+                    line: 2
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn atomic_declarations_test() {
+        let mut sniffer = Sniffer::from_str("att(a).").unwrap();
+        let declarations = Parser::parse_model("type key. fun h(bitstring): key. fun h: key.")
+            .unwrap()
+            .declarations
+            .into_iter()
+            .map(|(declaration, _)| declaration)
+            .collect();
+        assert!(sniffer.add_declarations(declarations).is_err());
+
+        // The valid declarations were not added either
+        let declarations = Parser::parse_model("type key. fun h: key.")
+            .unwrap()
+            .declarations
+            .into_iter()
+            .map(|(declaration, _)| declaration)
+            .collect();
+        assert!(sniffer.add_declarations(declarations).is_ok());
+    }
+
+    #[test]
+    fn incremental_saturation_test() {
+        let mut sniffer =
+            Sniffer::from_str("att(senc(X, Y)) /\\ att(Y) => att(X). att(senc(s, k)).").unwrap();
+        let query = Parser::parse_query("att(s).").unwrap();
+        assert!(sniffer.find(&query).is_err());
+
+        // Only the added rule is left to process
+        sniffer
+            .add_rule(Parser::parse_rules("att(k).").unwrap().remove(0))
+            .unwrap();
+        let state = sniffer.saturations.values().next().unwrap();
+        assert_eq!(state.unprocessed.len(), 1);
+        let processed = state.processed.len();
+
+        assert!(sniffer.find(&query).is_ok());
+        let state = sniffer.saturations.values().next().unwrap();
+        assert!(state.processed.len() > processed);
+    }
+
+    #[test]
+    fn add_rules_test() {
+        let mut sniffer = Sniffer::from_str("att(senc(X, Y)) /\\ att(Y) => att(X).").unwrap();
+        sniffer
+            .add_rules(Parser::parse_rules("att(k). att(senc(s, k)).").unwrap())
+            .unwrap();
+        assert!(sniffer
+            .find(&Parser::parse_query("att(s).").unwrap())
+            .is_ok());
+        assert!(sniffer
+            .add_rule(Parser::parse_rules("att(senc(k)).").unwrap().remove(0))
+            .is_err());
+    }
+
+    #[test]
+    fn saturated_lookup_test() {
+        let mut sniffer = Sniffer::from_str(
+            "att(senc(X, Y)) /\\ att(Y) => att(X). att(X) /\\ att(Y) => att(pair(X, Y)). att(senc(s, k)). att(k).",
+        )
+        .unwrap();
+        sniffer.saturate();
+        let processed = sniffer.saturations[&Strategy::Global].processed.len();
+
+        // Queries are looked up without saturating again, whatever their predicate
+        let tree = sniffer
+            .find(&Parser::parse_query("att(pair(s, k)).").unwrap())
+            .ok()
+            .unwrap();
+        assert_eq!(tree.root().to_string(), "att(pair(s, k))");
+        assert!(sniffer
+            .find(&Parser::parse_query("att(pair(s, t)).").unwrap())
+            .is_err());
+        assert!(sniffer
+            .find(&Parser::parse_query("ev(s).").unwrap())
+            .is_err());
+        assert_eq!(sniffer.saturations.len(), 1);
+        assert_eq!(
+            sniffer.saturations[&Strategy::Global].processed.len(),
+            processed
+        );
+
+        // Added rules are saturated on the next query
+        sniffer
+            .add_rule(Parser::parse_rules("att(s) => ev(s).").unwrap().remove(0))
+            .unwrap();
+        assert!(sniffer
+            .find(&Parser::parse_query("ev(s).").unwrap())
+            .is_ok());
+    }
+
+    #[test]
+    fn event_query_test() {
+        // The attacker's knowledge in the premises of events must be derived as well
+        let mut sniffer = Sniffer::new("examples/nspk.pif").unwrap();
+        let event = Parser::parse_query("event(end(pub(ska), pub(skb), nb[na[ski]])).").unwrap();
+        let Ok(tree) = sniffer.find(&event) else {
+            panic!("Expected the end event to be reachable")
+        };
+        assert_eq!(
+            tree.root().to_string(),
+            "event(end(pub(ska), pub(skb), nb[na[ski]]))"
+        );
+        let never = Parser::parse_query("event(end(pub(ska), pub(ska), nb[na[ski]])).").unwrap();
+        assert!(sniffer.find(&never).is_err());
+    }
+
+    #[test]
+    fn statistics_test() {
+        let mut sniffer = Sniffer::new("examples/nspk.pif").unwrap();
+        sniffer.saturate();
+        let stats = sniffer.statistics();
+        assert_eq!(
+            stats.generated,
+            stats.kept + stats.duplicates + stats.tautologies + stats.rejected
+        );
+        assert_eq!(
+            stats.resolutions_attempted,
+            stats.resolutions_successful + stats.unification_failures.values().sum::<usize>()
+        );
+        assert_eq!(stats.kept, stats.predicates.values().sum::<usize>());
+
+        // Looking a query up resolves rules with facts without generating any rule
+        let (generated, attempted) = (stats.generated, stats.resolutions_attempted);
+        let query = Parser::parse_query("att(nb[na[ski]]).").unwrap();
+        assert!(sniffer.find(&query).is_ok());
+        let stats = sniffer.statistics();
+        assert_eq!(stats.generated, generated);
+        assert_eq!(stats.resolutions_attempted, attempted);
+        assert!(stats.lookup_resolutions_successful > 0);
+
+        sniffer.reset_statistics();
+        assert_eq!(sniffer.statistics().generated, 0);
+    }
+
+    #[test]
+    fn bounded_saturation_test() {
+        let model = "att(senc(X, k)) => att(senc(senc(X, k), k)). att(senc(a, k)). att(senc(X, Y)) /\\ att(Y) => att(X).";
+        let mut sniffer = Sniffer::from_str(model).unwrap();
+        sniffer.set_bound(Some(Bound::Reject {
+            depth: 4,
+            size: usize::MAX,
+        }));
+        let secret = Parser::parse_query("att(b).").unwrap();
+        assert!(matches!(
+            sniffer.find(&secret),
+            Err(SaturationFailure::Incomplete)
+        ));
+        let shallow = Parser::parse_query("att(senc(senc(a, k), k)).").unwrap();
+        assert!(!sniffer.find(&shallow).ok().unwrap().is_possibly_spurious());
+
+        let mut sniffer = Sniffer::from_str(model).unwrap();
+        sniffer.set_bound(Some(Bound::Approximate { depth: 4 }));
+        assert!(matches!(
+            sniffer.find(&secret),
+            Err(SaturationFailure::Saturated)
+        ));
+        let deep = Parser::parse_query("att(senc(senc(senc(senc(a, k), k), k), k)).").unwrap();
+        assert!(sniffer.find(&deep).ok().unwrap().is_possibly_spurious());
+    }
+
+    #[test]
+    fn unbounded_after_bound_test() {
+        let mut sniffer =
+            Sniffer::from_str("att(a). att(X) => att(h(X)). att(h(h(h(c)))) => att(secret).")
+                .unwrap();
+        let secret = Parser::parse_query("att(secret).").unwrap();
+        sniffer.set_bound(Some(Bound::Approximate { depth: 2 }));
+        assert!(sniffer.find(&secret).ok().unwrap().is_possibly_spurious());
+
+        // Rules approximated under the previous bound must not be reused
+        sniffer.set_bound(None);
+        assert!(sniffer.find(&secret).is_err());
+    }
+
+    #[test]
+    fn generalisations_test() {
+        let mut sniffer = Sniffer::from_str("p(h(X)) => p(h(h(X))). p(h(a)).").unwrap();
+        sniffer.set_bound(Some(Bound::Reject {
+            depth: 4,
+            size: usize::MAX,
+        }));
+        let _ = sniffer.find(&Parser::parse_query("p(b).").unwrap());
+
+        // Generalising does not allocate variables of the sniffer
+        let next = sniffer.id_server.variables().scratch().fresh();
+        let generalisations = sniffer.generalisations();
+        assert_eq!(sniffer.id_server.variables().scratch().fresh(), next);
+        assert_eq!(generalisations, sniffer.generalisations());
+
+        let [(rule, count)] = &generalisations[..] else {
+            panic!("Expected a single family of derived rules")
+        };
+        let variable = sniffer.id_server.name_of(&next).unwrap();
+        assert_eq!(rule.to_string(), format!("p(h(h({variable})))"));
+        assert_eq!(*count, 2);
+    }
+
+    #[test]
+    fn shared_derivation_test() {
+        let mut sniffer = Sniffer::from_str(
+            "att(X) /\\ att(Y) => att(pair(X, Y)). att(senc(X, Y)) /\\ att(Y) => att(X). att(senc(k, l)). att(l).",
+        )
+        .unwrap();
+        let Ok(tree) = sniffer.find(&Parser::parse_query("att(pair(k, k)).").unwrap()) else {
+            panic!("Expected att(pair(k, k)) to be derivable")
+        };
+
+        // Both uses of att(k) point to the same node
+        let mut uses = vec![];
+        let mut to_visit = vec![&tree];
+        while let Some(node) = to_visit.pop() {
+            if node.root().to_string() == "att(k)" {
+                uses.push(node);
+            }
+            to_visit.extend(node.subtrees());
+        }
+        assert_eq!(uses.len(), 2);
+        assert!(uses[0].shares_root(uses[1]));
+    }
+
+    #[test]
+    fn variant_derivations_test() {
+        let mut sniffer = Sniffer::from_str(
+            "att(X) => att(f(X)). att(X) => att(g(X)). att(f(Y)) => att(h(Y)). att(g(Z)) => att(h(Z)).",
+        )
+        .unwrap();
+        sniffer.saturate();
+
+        // att(X) => att(h(X)) is derived twice, through f and through g, with other variables
+        let root = Parser::parse_rules("att(V) => att(h(V)).")
+            .unwrap()
+            .remove(0);
+        let trees = sniffer.derivation_trees(&root, 3);
+        assert_eq!(trees.len(), 2);
+        assert!(trees.iter().all(|t| t.subtrees().len() == 2));
+    }
+
+    #[test]
+    fn non_ground_certificate_test() {
+        let source = "att(X) => att(f(X)). att(f(Y)) => att(h(Y)).";
+        let mut sniffer = Sniffer::from_str(source).unwrap();
+        sniffer.saturate();
+
+        // Rules are certified up to renaming, while underivable ones have no certificate
+        let derived = Parser::parse_rules("att(V) => att(h(V)).")
+            .unwrap()
+            .remove(0);
+        let certificate = sniffer.certificate(&derived).unwrap();
+        let rules = Parser::parse_rules(source).unwrap();
+        assert!(check(&certificate, &rules).is_ok());
+        let underivable = Parser::parse_rules("att(V) => att(g(V)).")
+            .unwrap()
+            .remove(0);
+        assert!(sniffer.certificate(&underivable).is_none());
+    }
+
+    #[test]
+    fn cyclic_derivation_test() {
+        let mut sniffer =
+            Sniffer::from_str("att(a). att(a) => att(b). att(b) => att(c). att(c) => att(b).")
+                .unwrap();
+        sniffer.saturate();
+        let root = Parser::parse_rules("att(b).").unwrap().remove(0);
+        let trees = sniffer.derivation_trees(&root, 2);
+        assert_eq!(trees.len(), 2);
+
+        // The second derivation goes through att(c), itself derived from att(b)
+        let mut origins = vec![];
+        let mut to_visit = vec![&trees[1]];
+        while let Some(node) = to_visit.pop() {
+            origins.extend(node.origin());
+            to_visit.extend(node.subtrees());
+        }
+        assert!(origins.contains(&&Origin::Cycle));
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_saturation_test() {
+        // Rules are compared up to the naming of their variables
+        let saturated = |parallel| {
+            let mut sniffer = Sniffer::new("examples/nspk.pif").unwrap();
+            sniffer.set_parallel(parallel);
+            sniffer.saturate();
+            sniffer.saturations[&Strategy::Global]
+                .processed
+                .iter()
+                .map(|r| r.canonical())
+                .collect::<FxHashSet<_>>()
+        };
+        assert_eq!(saturated(false), saturated(true));
+    }
 }
@@ -0,0 +1,234 @@
+//! Lint module
+//! Spots rules which are valid but most likely modelling mistakes, as they silently
+//! make analyses meaningless
+use crate::ast::{Atom, InnerAtom, InnerRule, Rule, Span, Term};
+use crate::identifiers::IdentifierServer;
+use crate::parser::Model;
+use itertools::Itertools;
+use rustc_hash::FxHashMap;
+use std::fmt::{Display, Formatter};
+
+/// A suspicious construct found in a model
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Lint {
+    /// A variable of the conclusion which no premise constrains, so that the rule concludes anything
+    UnboundVariable(String),
+    /// A function symbol used a single time in the whole model, which is often a typo
+    SingleUseSymbol(String),
+    /// A rule whose conclusion is one of its premises, which never derives anything
+    Tautology,
+    /// A premise no rule can conclude, so that the rule can never fire
+    DeadRule(Atom<String>),
+}
+impl Lint {
+    /// Returns the name of the lint, which identifies it in reports
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lint::UnboundVariable(_) => "unbound-variable",
+            Lint::SingleUseSymbol(_) => "single-use-symbol",
+            Lint::Tautology => "tautology",
+            Lint::DeadRule(_) => "dead-rule",
+        }
+    }
+}
+impl Display for Lint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Lint::UnboundVariable(x) => {
+                write!(f, "variable `{x}` of the conclusion appears in no premise")
+            }
+            Lint::SingleUseSymbol(symbol) => write!(f, "symbol `{symbol}` is only used once"),
+            Lint::Tautology => write!(f, "the conclusion of the rule is one of its premises"),
+            Lint::DeadRule(premise) => write!(
+                f,
+                "no rule concludes premise `{premise}`, so the rule never fires"
+            ),
+        }
+    }
+}
+
+/// A lint, along with the span of the rule it was found in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub lint: Lint,
+    pub span: Span,
+}
+impl Display for Warning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "warning[{}]: {}", self.lint.name(), self.lint)
+    }
+}
+
+/// Lints the rules of a model, in order of appearance
+pub fn lint(model: &Model) -> Vec<Warning> {
+    let mut warnings = vec![];
+    let dead = dead_premises(&model.rules);
+    let uses = symbol_uses(&model.rules);
+
+    for (i, (rule, span)) in model.rules.iter().enumerate() {
+        let mut lints = vec![];
+
+        let mut variables = vec![];
+        variables_of(&rule.conclusion, &mut variables);
+        for x in variables.into_iter().unique() {
+            let x = Term::Variable {
+                symbol: x.to_string(),
+            };
+            if !rule.premises.iter().any(|p| p.contains_variable(&x)) {
+                lints.push(Lint::UnboundVariable(x.to_string()))
+            }
+        }
+
+        for symbol in function_symbols(rule) {
+            if uses.get(symbol) == Some(&1) {
+                lints.push(Lint::SingleUseSymbol(symbol.to_string()))
+            }
+        }
+
+        if rule.premises.contains(&rule.conclusion) {
+            lints.push(Lint::Tautology)
+        }
+        if let Some(premise) = &dead[i] {
+            lints.push(Lint::DeadRule(premise.clone()))
+        }
+
+        warnings.extend(lints.into_iter().map(|lint| Warning { lint, span: *span }))
+    }
+    warnings
+}
+
+/// Lists the occurrences of function symbols in a rule, predicates excluded
+fn function_symbols(rule: &Rule<String>) -> Vec<&str> {
+    let mut symbols = vec![];
+    for atom in rule
+        .premises
+        .iter()
+        .chain(std::iter::once(&rule.conclusion))
+    {
+        atom.parameters.iter().for_each(|t| t.symbols(&mut symbols))
+    }
+    for c in &rule.constraints {
+        c.left.symbols(&mut symbols);
+        c.right.symbols(&mut symbols)
+    }
+    symbols.into_iter().map(|(s, _)| s.as_str()).collect()
+}
+
+/// Counts the uses of every function symbol in the model
+fn symbol_uses(rules: &[(Rule<String>, Span)]) -> FxHashMap<&str, usize> {
+    let mut uses = FxHashMap::default();
+    for symbol in rules.iter().flat_map(|(r, _)| function_symbols(r)) {
+        *uses.entry(symbol).or_default() += 1
+    }
+    uses
+}
+
+/// Finds, for every rule which may never fire, a premise that no rule which may fire concludes.
+/// Starting from axioms, a rule may fire if each of its premises unifies with the conclusion
+/// of a rule which may fire
+fn dead_premises(rules: &[(Rule<String>, Span)]) -> Vec<Option<Atom<String>>> {
+    let mut id_server = IdentifierServer::default();
+    let inner_rules: Vec<InnerRule> = rules
+        .iter()
+        .map(|(r, _)| r.to_inner(&mut id_server))
+        .collect();
+
+    let mut fires = vec![false; rules.len()];
+    let concluded = |p: &InnerAtom, fires: &[bool]| {
+        inner_rules
+            .iter()
+            .zip(fires)
+            .any(|(r, may_fire)| *may_fire && p.unify(&r.conclusion).is_some())
+    };
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (i, rule) in inner_rules.iter().enumerate() {
+            if !fires[i] && rule.premises.iter().all(|p| concluded(p, &fires)) {
+                fires[i] = true;
+                changed = true;
+            }
+        }
+    }
+
+    inner_rules
+        .iter()
+        .zip(rules)
+        .zip(&fires)
+        .map(|((inner, (rule, _)), may_fire)| {
+            let dead = inner
+                .premises
+                .iter()
+                .position(|p| !may_fire && !concluded(p, &fires));
+            dead.map(|p| rule.premises[p].clone())
+        })
+        .collect()
+}
+
+pub(crate) fn variables_of<'a>(atom: &'a Atom<String>, variables: &mut Vec<&'a str>) {
+    fn visit<'a>(t: &'a Term<String>, variables: &mut Vec<&'a str>) {
+        match t {
+            Term::Variable { symbol } => variables.push(symbol),
+            Term::Function { parameters, .. } => {
+                parameters.iter().for_each(|t| visit(t, variables))
+            }
+        }
+    }
+    atom.parameters.iter().for_each(|t| visit(t, variables))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    fn lints(source: &str) -> Vec<Lint> {
+        lint(&Parser::parse_model(source).unwrap())
+            .into_iter()
+            .map(|w| w.lint)
+            .collect()
+    }
+
+    #[test]
+    fn unbound_variable_test() {
+        assert_eq!(
+            lints("att(a). att(a) => att(pair(X, a)). att(pair(a, a))."),
+            vec![Lint::UnboundVariable("X".to_string())]
+        );
+    }
+
+    #[test]
+    fn single_use_symbol_test() {
+        assert_eq!(
+            lints("att(sen(X, Y)) => att(X). att(senc(a, b)) /\\ att(senc(b, a)) => att(a)."),
+            vec![
+                Lint::SingleUseSymbol("sen".to_string()),
+                Lint::DeadRule(Parser::parse_query("att(sen(X, Y)).").unwrap()),
+                Lint::DeadRule(Parser::parse_query("att(senc(a, b)).").unwrap()),
+            ]
+        );
+    }
+
+    #[test]
+    fn tautology_test() {
+        assert_eq!(
+            lints("att(X) /\\ att(Y) => att(X)."),
+            vec![
+                Lint::Tautology,
+                Lint::DeadRule(Parser::parse_query("att(X).").unwrap())
+            ]
+        );
+    }
+
+    #[test]
+    fn dead_rule_propagation_test() {
+        let lints = lints("att(k). step(X) => att(senc(X, k)). att(senc(X, k)) => att(X).");
+        assert_eq!(
+            lints,
+            vec![
+                Lint::DeadRule(Parser::parse_query("step(X).").unwrap()),
+                Lint::DeadRule(Parser::parse_query("att(senc(X, k)).").unwrap()),
+            ]
+        );
+    }
+}
@@ -1,24 +1,65 @@
 use itertools::Itertools;
-use logos_nom_bridge::Tokens;
 use ptree::{Color, Style};
-use sniffer::{Parser, Sniffer};
+use serde::Serialize;
+use sniffer::{
+    Atom, Bound, Certificate, CorrespondenceResult, DerivationTree, Engine, Parser, Rule,
+    SaturationFailure, Sniffer, SnifferError, Tracer, Verbosity,
+};
 use std::env;
 
 fn main() {
     let args = env::args().collect::<Vec<_>>();
+    if let [_, command, file] = &args[..] {
+        if command == "lint" {
+            match lint_file(file) {
+                Ok(warnings) if warnings.is_empty() => return,
+                Ok(warnings) => warnings.iter().for_each(|w| println!("{w}")),
+                Err(e) => eprintln!("failed to lint file: {e}"),
+            }
+            std::process::exit(1)
+        }
+    }
+    if let [_, command, model, proof] = &args[..] {
+        if command == "verify-proof" {
+            match verify_proof(model, proof) {
+                Ok(rule) => println!("proof verified: {rule}"),
+                Err(e) => {
+                    eprintln!("proof rejected: {e}");
+                    std::process::exit(1)
+                }
+            }
+            return;
+        }
+    }
+
+    let mut settings = Settings::default();
+    let mut file = None;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--json" => settings.json = true,
+            "--stats" => settings.stats = true,
+            #[cfg(feature = "parallel")]
+            "--parallel" => settings.parallel = true,
+            flag if flag.starts_with("--") => {
+                eprintln!("unknown flag: {flag}");
+                std::process::exit(1)
+            }
+            _ => file = Some(arg),
+        }
+    }
 
     // One argument (the file path) runs the REPL with basic axioms loaded in
     // Otherwise we just run the REPL with no axioms
-    let mut sniffer = if let Some(file) = args.get(1) {
-        if let Ok(from_file) = Sniffer::new(file) {
-            from_file
-        } else {
-            println!("failed to parse file");
+    let mut sniffer = if let Some(file) = file {
+        Sniffer::new(file).unwrap_or_else(|e| {
+            eprintln!("failed to load file: {e}");
             Sniffer::default()
-        }
+        })
     } else {
         Sniffer::default()
     };
+    #[cfg(feature = "parallel")]
+    sniffer.set_parallel(settings.parallel);
 
     let mut rl = rustyline::DefaultEditor::new().expect("failed to open repl");
     let prompt_style = Style {
@@ -33,49 +74,375 @@ fn main() {
         let command = words.next().unwrap();
         let query = words.join(" ");
 
-        match handle_command(command, &query, &mut sniffer) {
+        // Statistics are those of the last command that saturated the rule set
+        let saturating = SATURATING_COMMANDS.contains(&command);
+        if saturating {
+            sniffer.reset_statistics()
+        }
+
+        match handle_command(command, &query, &mut sniffer, &settings) {
             CommandResult::ParsingError => eprintln!("parsing error"),
             CommandResult::UnknownCommand => eprintln!("unknown command"),
             CommandResult::FileError => eprintln!("failed to open/write to file"),
-            CommandResult::NotFoundQuery => eprintln!("no result for query"),
+            CommandResult::NoCertificate => {
+                eprintln!("no certificate can be given for this derivation")
+            }
+            CommandResult::UnknownPhase(n) => eprintln!("the model has no phase {n}"),
+            CommandResult::LoadError(e) => eprintln!("failed to load file: {e}"),
+            CommandResult::RejectedRules(e) => eprintln!("rules rejected: {e}"),
+            CommandResult::NotFoundQuery => eprintln!("no result for query (see why-not)"),
+            CommandResult::IncompleteQuery => eprintln!(
+                "no result for query, but resolvents exceeding the bound were discarded, so it may still be derivable"
+            ),
             CommandResult::Quit => break,
             _ => (),
         }
+        if saturating && settings.stats {
+            print!("{}", sniffer.statistics())
+        }
     }
 }
 
+/// Commands which saturate the rule set
+const SATURATING_COMMANDS: [&str; 5] = ["query", "attack", "why-not", "correspondence", "saturate"];
+
+/// Checks a proof certificate against the rules of a `.pif` file, returning the proved rule
+fn verify_proof(model: &str, proof: &str) -> Result<Rule<String>, String> {
+    let model = std::fs::read_to_string(model).map_err(|e| e.to_string())?;
+    let rules = Parser::parse_rules(&model).map_err(|e| e.to_string())?;
+    let proof = std::fs::read_to_string(proof).map_err(|e| e.to_string())?;
+    let certificate: Certificate = serde_json::from_str(&proof).map_err(|e| e.to_string())?;
+
+    sniffer::check(&certificate, &rules)
+        .cloned()
+        .map_err(|e| e.to_string())
+}
+
+/// Lints the rules of a `.pif` file, returning the located warnings
+fn lint_file(file: &str) -> Result<Vec<String>, String> {
+    let source = std::fs::read_to_string(file).map_err(|e| e.to_string())?;
+    let model = Parser::parse_model(&source).map_err(|e| {
+        let (line, column) = e.span.line_col(&source);
+        format!("{file}:{line}:{column}: {e}")
+    })?;
+
+    Ok(sniffer::lint(&model)
+        .into_iter()
+        .map(|w| {
+            let (line, column) = w.span.line_col(&source);
+            format!("{file}:{line}:{column}: {w}")
+        })
+        .collect())
+}
+
+/// Settings given through command line flags
+#[derive(Default)]
+struct Settings {
+    /// Prints query results as JSON instead of trees
+    json: bool,
+    /// Prints saturation statistics after each query
+    stats: bool,
+    /// Spreads saturation over worker threads
+    #[cfg(feature = "parallel")]
+    parallel: bool,
+}
+
+/// JSON output of the `query` command
+#[derive(Serialize)]
+struct QueryReport<'a> {
+    query: &'a Atom<String>,
+    derivable: bool,
+    /// Whether the derivation goes through approximated rules
+    possibly_spurious: bool,
+    derivation: Option<&'a DerivationTree>,
+}
+
 enum CommandResult {
     OkCommand,
     ParsingError,
     UnknownCommand,
     Quit,
     FileError,
+    LoadError(SnifferError),
+    RejectedRules(SnifferError),
     NotFoundQuery,
+    IncompleteQuery,
+    NoCertificate,
+    UnknownPhase(usize),
+}
+impl From<SaturationFailure> for CommandResult {
+    fn from(value: SaturationFailure) -> Self {
+        match value {
+            SaturationFailure::Incomplete => CommandResult::IncompleteQuery,
+            _ => CommandResult::NotFoundQuery,
+        }
+    }
 }
-fn handle_command(command: &str, query: &str, sniffer: &mut Sniffer) -> CommandResult {
+/// Options that can be given to the `query` command before the queried atom
+#[derive(Default)]
+struct QueryOptions {
+    dot: Option<String>,
+    latex: Option<String>,
+    certificate: Option<String>,
+    json: bool,
+    phase: Option<usize>,
+    engine: Engine,
+}
+impl QueryOptions {
+    /// Splits `--option value` pairs from the rest of the query
+    fn parse(query: &str) -> Option<(QueryOptions, String)> {
+        let mut options = QueryOptions::default();
+        let mut words = query.split_whitespace().peekable();
+        while let Some(option) = words.next_if(|w| w.starts_with("--")) {
+            match option {
+                "--dot" => options.dot = Some(words.next()?.to_string()),
+                "--latex" => options.latex = Some(words.next()?.to_string()),
+                "--certificate" => options.certificate = Some(words.next()?.to_string()),
+                "--json" => options.json = true,
+                "--phase" => options.phase = Some(words.next()?.parse().ok()?),
+                "--engine" => options.engine = words.next()?.parse().ok()?,
+                _ => return None,
+            }
+        }
+        Some((options, words.join(" ")))
+    }
+}
+
+fn handle_command(
+    command: &str,
+    query: &str,
+    sniffer: &mut Sniffer,
+    settings: &Settings,
+) -> CommandResult {
     match command {
-        "load" => {
-            if let Ok(from_file) = Sniffer::new(query) {
+        "load" => match Sniffer::new(query) {
+            Ok(from_file) => {
+                let tracer = sniffer.set_tracer(None);
+                let bound = sniffer.bound();
                 *sniffer = from_file;
+                sniffer.set_tracer(tracer);
+                sniffer.set_bound(bound);
+                #[cfg(feature = "parallel")]
+                sniffer.set_parallel(settings.parallel);
                 CommandResult::OkCommand
+            }
+            Err(e) => CommandResult::LoadError(e),
+        },
+
+        "query" => {
+            let (options, query) = if let Some(parsed) = QueryOptions::parse(query) {
+                parsed
+            } else {
+                return CommandResult::ParsingError;
+            };
+            let query = if let Ok(query) = Parser::parse_query(&query) {
+                query
             } else {
-                CommandResult::FileError
+                return CommandResult::ParsingError;
+            };
+            let query = match options.phase {
+                Some(n) if sniffer.last_phase().is_some_and(|last| n <= last) => query.at_phase(n),
+                Some(n) => return CommandResult::UnknownPhase(n),
+                None => sniffer.in_last_phase(&query),
+            };
+
+            let result = sniffer.find_with(&query, options.engine);
+            if options.json || settings.json {
+                let report = QueryReport {
+                    query: &query,
+                    derivable: result.is_ok(),
+                    possibly_spurious: result.as_ref().is_ok_and(|t| t.is_possibly_spurious()),
+                    derivation: result.as_ref().ok(),
+                };
+                println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            }
+
+            match result {
+                Ok(derivation_tree) => {
+                    if !(options.json || settings.json) {
+                        ptree::print_tree(&derivation_tree).unwrap();
+                        if derivation_tree.is_possibly_spurious() {
+                            println!(
+                                "the derivation goes through approximated rules, so it may be spurious"
+                            )
+                        }
+                    }
+                    if let Some(file) = options.dot {
+                        if std::fs::write(file, derivation_tree.to_dot()).is_err() {
+                            return CommandResult::FileError;
+                        }
+                    }
+                    if let Some(file) = options.latex {
+                        if std::fs::write(file, derivation_tree.to_latex()).is_err() {
+                            return CommandResult::FileError;
+                        }
+                    }
+                    if let Some(file) = options.certificate {
+                        let Some(certificate) = sniffer.certificate(derivation_tree.root()) else {
+                            return CommandResult::NoCertificate;
+                        };
+                        let json = serde_json::to_string_pretty(&certificate).unwrap();
+                        if std::fs::write(file, json).is_err() {
+                            return CommandResult::FileError;
+                        }
+                    }
+                    CommandResult::OkCommand
+                }
+                Err(failure) => failure.into(),
             }
         }
 
-        "query" => {
-            let query = if let Ok(query) = Parser::parse_query(Tokens::new(query)) {
+        "attack" => {
+            let query = if let Ok(query) = Parser::parse_query(query) {
                 query
             } else {
                 return CommandResult::ParsingError;
             };
+            let query = sniffer.in_last_phase(&query);
 
-            if let Ok(derivation_tree) = sniffer.find(&query) {
-                ptree::print_tree(&derivation_tree).unwrap();
-                CommandResult::OkCommand
+            match sniffer.find(&query) {
+                Ok(derivation_tree) => {
+                    print!("{}", derivation_tree.attack_trace());
+                    if derivation_tree.is_possibly_spurious() {
+                        println!(
+                            "the attack goes through approximated rules, so it may be spurious"
+                        )
+                    }
+                    CommandResult::OkCommand
+                }
+                Err(failure) => failure.into(),
+            }
+        }
+
+        "why-not" => {
+            let query = if let Ok(query) = Parser::parse_query(query) {
+                query
             } else {
-                CommandResult::NotFoundQuery
+                return CommandResult::ParsingError;
+            };
+            let query = sniffer.in_last_phase(&query);
+
+            match sniffer.why_not(&query) {
+                Some(why_not) => print!("{why_not}"),
+                None => println!("{query} is derivable"),
             }
+            CommandResult::OkCommand
+        }
+
+        "correspondence" => {
+            let (injective, query) = match query.strip_prefix("--injective") {
+                Some(query) => (true, query),
+                None => (false, query),
+            };
+            let mut query = if let Ok(query) = Parser::parse_correspondence(query) {
+                query
+            } else {
+                return CommandResult::ParsingError;
+            };
+            query.injective = injective;
+
+            match sniffer.check_correspondence(&query) {
+                CorrespondenceResult::Holds => println!("{query} holds"),
+                CorrespondenceResult::Incomplete => println!(
+                    "{query} holds for derived events, but resolvents exceeding the bound were discarded, so it may be violated"
+                ),
+                CorrespondenceResult::Violated(violation) => {
+                    println!(
+                        "{query} is violated: {} without {}",
+                        violation.instance, violation.required
+                    );
+                    ptree::print_tree(&violation.derivation).unwrap();
+                    if violation.derivation.is_possibly_spurious() {
+                        println!(
+                            "the derivation goes through approximated rules, so it may be spurious"
+                        )
+                    }
+                    if violation.possibly_spurious {
+                        println!(
+                            "{} may be derivable through rules this check does not chain, so the violation may be spurious",
+                            violation.required
+                        )
+                    }
+                }
+                CorrespondenceResult::NotInjective(violation) => {
+                    let (i1, i2) = &violation.instances;
+                    println!(
+                        "{query} is not injective: {i1} and {i2} both come with {}",
+                        violation.required
+                    );
+                }
+            }
+            CommandResult::OkCommand
+        }
+
+        "add" => {
+            let rules = if let Ok(rules) = Parser::parse_rules(query) {
+                rules
+            } else {
+                return CommandResult::ParsingError;
+            };
+
+            match sniffer.add_rules(rules) {
+                Ok(()) => CommandResult::OkCommand,
+                Err(e) => CommandResult::RejectedRules(e),
+            }
+        }
+
+        "saturate" => {
+            let size = sniffer.saturate();
+            println!("saturated: {size} rules");
+            CommandResult::OkCommand
+        }
+
+        "bound" => {
+            let numbers = query
+                .split_whitespace()
+                .skip(1)
+                .map(|w| w.parse::<usize>())
+                .collect::<Result<Vec<_>, _>>();
+            let bound = match (query.split_whitespace().next(), numbers.as_deref()) {
+                (Some("reject"), Ok([depth])) => Some(Bound::Reject {
+                    depth: *depth,
+                    size: usize::MAX,
+                }),
+                (Some("reject"), Ok([depth, size])) => Some(Bound::Reject {
+                    depth: *depth,
+                    size: *size,
+                }),
+                (Some("approximate"), Ok([depth])) => Some(Bound::Approximate { depth: *depth }),
+                (Some("off"), Ok([])) => None,
+                _ => return CommandResult::ParsingError,
+            };
+            sniffer.set_bound(bound);
+            CommandResult::OkCommand
+        }
+
+        "trace" => {
+            let mut words = query.split_whitespace();
+            match words.next() {
+                Some("on") => {
+                    let mut words = words.peekable();
+                    let verbosity = match words.next_if(|w| w.parse::<usize>().is_ok()) {
+                        None | Some("2") => Verbosity::Resolvents,
+                        Some("1") => Verbosity::Selections,
+                        Some("3") => Verbosity::Discarded,
+                        Some(_) => return CommandResult::ParsingError,
+                    };
+                    let tracer = Tracer::new(verbosity, |event| println!("{event}"))
+                        .with_predicates(words.map(String::from).collect());
+                    sniffer.set_tracer(Some(tracer));
+                }
+                Some("off") => {
+                    sniffer.set_tracer(None);
+                }
+                _ => return CommandResult::ParsingError,
+            }
+            CommandResult::OkCommand
+        }
+
+        "stats" => {
+            print!("{}", sniffer.statistics());
+            CommandResult::OkCommand
         }
 
         "rules" => {
@@ -83,31 +450,67 @@ fn handle_command(command: &str, query: &str, sniffer: &mut Sniffer) -> CommandR
             CommandResult::OkCommand
         }
         "derivation" => {
-            if query.is_empty() {
-                let rules: Vec<_> = sniffer.iter_rules().collect();
-
-                for tree in rules
-                    .into_iter()
-                    .filter_map(|r| sniffer.derivation_tree(&r))
-                {
-                    ptree::print_tree(&tree).unwrap()
+            let (count, query) = match query.split_whitespace().collect_vec()[..] {
+                ["--alternatives", count, ..] => {
+                    if let Ok(count) = count.parse() {
+                        (count, query.split_whitespace().skip(2).join(" "))
+                    } else {
+                        return CommandResult::ParsingError;
+                    }
                 }
-                CommandResult::OkCommand
+                _ => (1, query.to_string()),
+            };
+
+            let rules = if query.is_empty() {
+                sniffer.iter_rules().collect()
+            } else if let Ok(rules) = Parser::parse_rules(&query) {
+                rules
             } else {
-                let rules = if let Ok(rules) = Parser::parse_rules(Tokens::new(query)) {
-                    rules
-                } else {
-                    return CommandResult::ParsingError;
-                };
+                return CommandResult::ParsingError;
+            };
+
+            for tree in rules
+                .into_iter()
+                .flat_map(|r| sniffer.derivation_trees(&r, count))
+            {
+                ptree::print_tree(&tree).unwrap()
+            }
+            CommandResult::OkCommand
+        }
 
-                for tree in rules
-                    .into_iter()
-                    .filter_map(|r| sniffer.derivation_tree(&r))
-                {
-                    ptree::print_tree(&tree).unwrap()
+        "lint" => {
+            let file = if query.is_empty() {
+                if let Some(file) = sniffer.file() {
+                    file.to_string()
+                } else {
+                    return CommandResult::FileError;
                 }
-                CommandResult::OkCommand
+            } else {
+                query.to_string()
+            };
+
+            match lint_file(&file) {
+                Ok(warnings) => warnings.iter().for_each(|w| println!("{w}")),
+                Err(e) => eprintln!("failed to lint file: {e}"),
             }
+            CommandResult::OkCommand
+        }
+
+        "slice" => {
+            let query = if let Ok(query) = Parser::parse_query(query) {
+                query
+            } else {
+                return CommandResult::ParsingError;
+            };
+            print!("{}", sniffer.slice(&query));
+            CommandResult::OkCommand
+        }
+
+        "generalise" => {
+            for (rule, count) in sniffer.generalisations() {
+                println!("{rule}  ({count} derived)");
+            }
+            CommandResult::OkCommand
         }
 
         "quit" => CommandResult::Quit,
@@ -3,33 +3,246 @@
 //! by walking though said vector
 
 use crate::ast::*;
+use crate::attack::ATTACKER_PREDICATE;
+use crate::correspondence::Correspondence;
 use crate::lexer::Lexeme;
+use crate::phase;
+use itertools::{Either, Itertools};
 use logos_nom_bridge::Tokens;
 use nom::branch::alt;
+use nom::combinator::{map, opt};
 use nom::multi::{many0, separated_list0};
-use nom::sequence::{delimited, pair, terminated};
+use nom::sequence::{delimited, pair, preceded, terminated, tuple};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
 
-#[derive(Debug)]
-pub struct ParserError;
+/// A parsing error, located at the unexpected token
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParserError {
+    pub span: Span,
+    /// The unexpected token, or `None` if the input ended too early
+    pub found: Option<String>,
+}
+impl Display for ParserError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.found {
+            Some(token) => write!(f, "unexpected `{token}`"),
+            None => write!(f, "unexpected end of input"),
+        }
+    }
+}
+
+/// A parsed `.pif` file, along with the span of its declarations and rules
+#[derive(Debug, Default)]
+pub struct Model {
+    pub declarations: Vec<(Declaration, Span)>,
+    pub rules: Vec<(Rule<String>, Span)>,
+    /// Last phase of a phased model, whose rules were translated
+    pub last_phase: Option<usize>,
+}
+enum Item {
+    Declaration(Declaration),
+    Rule(Rule<String>),
+    /// `phase 1.`, the following rules belonging to that phase
+    Phase(usize),
+    /// `compromise ska.`, the attacker knowing the term from the current phase on
+    Compromise(Term<String>),
+    /// `capabilities.`, the following rules up to the next phase being attacker capabilities
+    Capabilities,
+}
 
 type Input<'source> = Tokens<'source, Lexeme>;
+/// Atoms and disequality constraints among the premises of a rule
+type Premises = (Vec<Atom<String>>, Vec<Disequality<String>>);
 
 pub struct Parser;
 impl Parser {
-    pub fn parse_query(input: Input) -> Result<Atom<String>, ParserError> {
-        match terminated(Self::parse_atom, Lexeme::Stop)(input) {
-            Ok((_, atom)) => Ok(atom),
-            Err(_) => Err(ParserError),
+    pub fn parse_query(source: &str) -> Result<Atom<String>, ParserError> {
+        match terminated(Self::parse_atom, Lexeme::Stop)(Tokens::new(source)) {
+            Ok((rest, atom)) => Self::expect_end(source, rest).map(|_| atom),
+            Err(e) => Err(Self::error(source, e)),
+        }
+    }
+    /// Parses a correspondence query `event(...) => event(...).`, which is not injective
+    pub fn parse_correspondence(source: &str) -> Result<Correspondence, ParserError> {
+        let mut parser = tuple((
+            terminated(Self::parse_atom, Lexeme::Implies),
+            terminated(Self::parse_atom, Lexeme::Stop),
+        ));
+        match parser(Tokens::new(source)) {
+            Ok((rest, (event, required))) => {
+                Self::expect_end(source, rest).map(|_| Correspondence {
+                    event,
+                    required,
+                    injective: false,
+                })
+            }
+            Err(e) => Err(Self::error(source, e)),
+        }
+    }
+    pub fn parse_rules(source: &str) -> Result<Vec<Rule<String>>, ParserError> {
+        Ok(Self::parse_model(source)?
+            .rules
+            .into_iter()
+            .map(|(rule, _)| rule)
+            .collect())
+    }
+    /// Parses the declarations and rules of `source` along with their span. Models using
+    /// phases are translated into plain ones (see the `phase` module)
+    pub fn parse_model(source: &str) -> Result<Model, ParserError> {
+        let items = match many0(Self::parse_located_item(source))(Tokens::new(source)) {
+            Ok((rest, items)) => Self::expect_end(source, rest).map(|_| items)?,
+            Err(e) => return Err(Self::error(source, e)),
+        };
+
+        let mut model = Model::default();
+        let mut rules = vec![];
+        let mut current = 0;
+        let mut capabilities = false;
+        let mut directives = BTreeMap::new();
+        let mut phased = false;
+        for (item, span) in items {
+            match item {
+                Item::Declaration(declaration) => model.declarations.push((declaration, span)),
+                Item::Rule(rule) => rules.push((rule, span, (!capabilities).then_some(current))),
+                Item::Phase(n) => {
+                    current = n;
+                    capabilities = false;
+                    directives.entry(n).or_insert(span);
+                    phased = true;
+                }
+                Item::Capabilities => capabilities = true,
+                Item::Compromise(term) => {
+                    let rule = Rule {
+                        premises: vec![],
+                        conclusion: Atom {
+                            symbol: ATTACKER_PREDICATE.to_string(),
+                            parameters: vec![term],
+                        },
+                        constraints: vec![],
+                    };
+                    rules.push((rule, span, Some(current)));
+                    phased = true;
+                }
+            }
+        }
+        model.rules = if phased {
+            model.last_phase = Some(directives.keys().last().copied().unwrap_or(0));
+            phase::translate(rules, &directives)
+        } else {
+            rules
+                .into_iter()
+                .map(|(rule, span, _)| (rule, span))
+                .collect()
+        };
+        Ok(model)
+    }
+    fn parse_located_item<'s>(
+        source: &'s str,
+    ) -> impl FnMut(Input<'s>) -> nom::IResult<Input<'s>, (Item, Span)> {
+        move |input| {
+            let start = Self::offset(source, &input);
+            let (rest, item) = Self::parse_item(input)?;
+            let end = source.len() - rest.len();
+            Ok((rest, (item, Span { start, end })))
+        }
+    }
+    fn parse_item(input: Input) -> nom::IResult<Input, Item> {
+        // Keywords start declarations, so that errors are reported within them
+        match input.peek() {
+            Some((Lexeme::Type | Lexeme::Fun, _)) => {
+                map(Self::parse_declaration, Item::Declaration)(input)
+            }
+            Some((Lexeme::Phase, _)) => map(
+                delimited(Lexeme::Phase, Self::parse_number, Lexeme::Stop),
+                Item::Phase,
+            )(input),
+            Some((Lexeme::Compromise, _)) => map(
+                delimited(Lexeme::Compromise, Self::parse_term, Lexeme::Stop),
+                Item::Compromise,
+            )(input),
+            Some((Lexeme::Capabilities, _)) => {
+                map(pair(Lexeme::Capabilities, Lexeme::Stop), |_| {
+                    Item::Capabilities
+                })(input)
+            }
+            _ => map(Self::parse_rule, Item::Rule)(input),
+        }
+    }
+
+    fn parse_declaration(input: Input) -> nom::IResult<Input, Declaration> {
+        alt((
+            map(
+                delimited(Lexeme::Type, Self::parse_constant, Lexeme::Stop),
+                Declaration::Type,
+            ),
+            map(
+                delimited(
+                    Lexeme::Fun,
+                    tuple((
+                        Self::parse_constant,
+                        opt(delimited(
+                            Lexeme::OpeningParentheses,
+                            separated_list0(Lexeme::Comma, Self::parse_constant),
+                            Lexeme::ClosingParentheses,
+                        )),
+                        preceded(Lexeme::Colon, Self::parse_constant),
+                    )),
+                    Lexeme::Stop,
+                ),
+                |(symbol, parameters, result)| Declaration::Function {
+                    symbol,
+                    parameters: parameters.unwrap_or_default(),
+                    result,
+                },
+            ),
+        ))(input)
+    }
+    /// Fails on the first token left in `rest`, if any. As `many0` stops at the first item
+    /// it cannot parse, parsing that item again tells where it went wrong
+    fn expect_end(source: &str, rest: Input) -> Result<(), ParserError> {
+        match rest.peek() {
+            None => Ok(()),
+            Some(_) => match Self::parse_item(rest.clone()) {
+                Err(e) => Err(Self::error(source, e)),
+                Ok(_) => Err(Self::error_at(source, &rest)),
+            },
         }
     }
-    pub fn parse_rules(input: Input) -> Result<Vec<Rule<String>>, ParserError> {
-        match many0(Self::parse_rule)(input) {
-            Ok((_, rules)) => Ok(rules),
-            Err(_) => Err(ParserError),
+    fn error(source: &str, error: nom::Err<nom::error::Error<Input>>) -> ParserError {
+        match error {
+            nom::Err::Error(e) | nom::Err::Failure(e) => Self::error_at(source, &e.input),
+            nom::Err::Incomplete(_) => ParserError {
+                span: Span {
+                    start: source.len(),
+                    end: source.len(),
+                },
+                found: None,
+            },
+        }
+    }
+    fn error_at(source: &str, input: &Input) -> ParserError {
+        let start = Self::offset(source, input);
+        let found = input.peek().map(|(_, slice)| slice.to_string());
+        ParserError {
+            span: Span {
+                start,
+                end: start + found.as_ref().map_or(0, |t| t.len()),
+            },
+            found,
+        }
+    }
+
+    /// Returns the offset in `source` of the next token of `input`
+    fn offset(source: &str, input: &Input) -> usize {
+        match input.peek() {
+            Some((_, slice)) => slice.as_ptr() as usize - source.as_ptr() as usize,
+            None => source.len(),
         }
     }
     fn parse_rule(input: Input) -> nom::IResult<Input, Rule<String>> {
-        if let Ok((rest, premisses)) = terminated(Self::parse_atoms, Lexeme::Implies)(input.clone())
+        if let Ok((rest, (premisses, constraints))) =
+            terminated(Self::parse_premises, Lexeme::Implies)(input.clone())
         {
             let (rest, conclusion) = terminated(Self::parse_atom, Lexeme::Stop)(rest)?;
             Ok((
@@ -37,6 +250,7 @@ impl Parser {
                 Rule {
                     premises: premisses,
                     conclusion,
+                    constraints,
                 },
             ))
         } else {
@@ -46,13 +260,29 @@ impl Parser {
                 Rule {
                     premises: vec![],
                     conclusion,
+                    constraints: vec![],
                 },
             ))
         }
     }
 
-    fn parse_atoms(input: Input) -> nom::IResult<Input, Vec<Atom<String>>> {
-        separated_list0(Lexeme::And, Self::parse_atom)(input)
+    /// Parses premises, which are either atoms or disequality constraints such as `X <> a`
+    fn parse_premises(input: Input) -> nom::IResult<Input, Premises> {
+        let (rest, premises) = separated_list0(
+            Lexeme::And,
+            alt((
+                map(Self::parse_disequality, Either::Right),
+                map(Self::parse_atom, Either::Left),
+            )),
+        )(input)?;
+        let (atoms, constraints) = premises.into_iter().partition_map(|p| p);
+        Ok((rest, (atoms, constraints)))
+    }
+    fn parse_disequality(input: Input) -> nom::IResult<Input, Disequality<String>> {
+        map(
+            tuple((Self::parse_term, Lexeme::Different, Self::parse_term)),
+            |(left, _, right)| Disequality { left, right },
+        )(input)
     }
     fn parse_atom(input: Input) -> nom::IResult<Input, Atom<String>> {
         let (rest, (symbol, terms)) = pair(
@@ -84,6 +314,21 @@ impl Parser {
     }
     fn parse_application(input: Input) -> nom::IResult<Input, Term<String>> {
         let (rest, symbol) = Self::parse_constant(input)?;
+        // Session-indexed names `na[S]` are functions of their indices
+        if let Ok((rest, terms)) = delimited(
+            Lexeme::OpeningBracket,
+            Self::parse_terms,
+            Lexeme::ClosingBracket,
+        )(rest.clone())
+        {
+            return Ok((
+                rest,
+                Term::Function {
+                    symbol: symbol + NAME_SUFFIX,
+                    parameters: terms,
+                },
+            ));
+        }
         if let Ok((rest, terms)) = delimited(
             Lexeme::OpeningParentheses,
             Self::parse_terms,
@@ -114,10 +359,44 @@ impl Parser {
     }
     logos_nom_bridge::data_variant_parser! {
         fn parse_integer(input) -> Result<Term<String>>;
-        pattern = Lexeme::Integer(_i) => Term::Variable { symbol: String::from("todo") };
+        pattern = Lexeme::Integer(i) => Term::Function { symbol: i.to_string(), parameters: vec![] };
+    }
+    logos_nom_bridge::data_variant_parser! {
+        fn parse_number(input) -> Result<usize>;
+        pattern = Lexeme::Integer(i) => i;
     }
     logos_nom_bridge::data_variant_parser! {
         fn parse_constant(input) -> Result<String>;
         pattern = Lexeme::Constant(symbol) => symbol;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_error_location_test() {
+        let source = "att(a).\natt(pair(a, b) => att(a).";
+        let error = Parser::parse_rules(source).unwrap_err();
+        assert_eq!(error.found.as_deref(), Some("=>"));
+        assert_eq!(error.span.line_col(source), (2, 16));
+    }
+
+    #[test]
+    fn parse_name_test() {
+        let atom = Parser::parse_query("att(aenc(nb[na[X]], pub(skb))).").unwrap();
+        let Term::Function { parameters, .. } = &atom.parameters[0] else {
+            panic!("Expected a function")
+        };
+        assert_eq!(parameters[0].symbol(), "nb[]");
+        assert!(parameters[0].is_name());
+        assert_eq!(atom.to_string(), "att(aenc(nb[na[X]], pub(skb)))");
+    }
+
+    #[test]
+    fn parse_error_end_of_input_test() {
+        let error = Parser::parse_query("att(a)").unwrap_err();
+        assert_eq!(error.found, None);
+    }
+}
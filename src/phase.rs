@@ -0,0 +1,153 @@
+//! Phase module
+//! Phases split a protocol in successive stages, such as before and after the compromise of
+//! long-term keys. A phased model is translated into a plain one, where `att_phase(n, M)` stands
+//! for the attacker knowing `M` in phase `n`, its knowledge carrying forward to later phases
+use crate::ast::{Atom, Rule, Span, Term};
+use crate::attack::ATTACKER_PREDICATE;
+use crate::lint::variables_of;
+use std::collections::BTreeMap;
+
+/// Symbol of the predicate representing the attacker's knowledge in a given phase
+pub const PHASE_PREDICATE: &str = "att_phase";
+
+/// Returns the term standing for phase `n`
+fn phase(n: usize) -> Term<String> {
+    Term::Function {
+        symbol: n.to_string(),
+        parameters: vec![],
+    }
+}
+
+impl Atom<String> {
+    /// Scopes an atom of the attacker's knowledge to phase `n`, other atoms being left as is
+    pub fn in_phase(&self, n: Term<String>) -> Atom<String> {
+        match &self.parameters[..] {
+            [message] if self.symbol == ATTACKER_PREDICATE => Atom {
+                symbol: PHASE_PREDICATE.to_string(),
+                parameters: vec![n, message.clone()],
+            },
+            _ => self.clone(),
+        }
+    }
+
+    /// Scopes an atom of the attacker's knowledge to the given phase
+    pub fn at_phase(&self, n: usize) -> Atom<String> {
+        self.in_phase(phase(n))
+    }
+}
+
+/// Translates the rules of a phased model, each given along with its phase, or `None` for the
+/// attacker capabilities of the `capabilities` section. Those hold in every phase, while the
+/// other rules only speak of the knowledge of their own phase. Knowledge carries forward from
+/// each phase to the next one, through rules located at the `phase` directive starting it,
+/// given in `directives`
+pub fn translate(
+    rules: Vec<(Rule<String>, Span, Option<usize>)>,
+    directives: &BTreeMap<usize, Span>,
+) -> Vec<(Rule<String>, Span)> {
+    let mut translated = rules
+        .into_iter()
+        .map(|(rule, span, n)| {
+            let n = match n {
+                Some(n) => phase(n),
+                None => phase_variable(&rule),
+            };
+            let rule = Rule {
+                conclusion: rule.conclusion.in_phase(n.clone()),
+                premises: rule
+                    .premises
+                    .iter()
+                    .map(|p| p.in_phase(n.clone()))
+                    .collect(),
+                constraints: rule.constraints,
+            };
+            (rule, span)
+        })
+        .collect::<Vec<_>>();
+
+    let last = directives.keys().last().copied().unwrap_or(0);
+    for n in 1..=last {
+        let (_, span) = directives.range(n..).next().unwrap();
+        let x = Term::Variable {
+            symbol: "X".to_string(),
+        };
+        let knowledge = Atom {
+            symbol: ATTACKER_PREDICATE.to_string(),
+            parameters: vec![x],
+        };
+        let rule = Rule {
+            premises: vec![knowledge.at_phase(n - 1)],
+            conclusion: knowledge.at_phase(n),
+            constraints: vec![],
+        };
+        translated.push((rule, *span))
+    }
+    translated
+}
+
+/// Returns a variable standing for any phase, which does not occur in `rule`
+fn phase_variable(rule: &Rule<String>) -> Term<String> {
+    let mut variables = vec![];
+    rule.premises
+        .iter()
+        .chain(std::iter::once(&rule.conclusion))
+        .for_each(|a| variables_of(a, &mut variables));
+
+    let occurs = |symbol: &String| {
+        let x = Term::Variable {
+            symbol: symbol.clone(),
+        };
+        variables.contains(&symbol.as_str())
+            || rule.constraints.iter().any(|c| c.contains_variable(&x))
+    };
+
+    let mut symbol = String::from("Phase");
+    while occurs(&symbol) {
+        symbol.push('\'')
+    }
+    Term::Variable { symbol }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Parser, Sniffer};
+
+    #[test]
+    fn translate_test() {
+        let model = Parser::parse_model(
+            "capabilities. att(X) => att(pub(X)). phase 0. att(X) => att(h(X)). att(k) => ev(k). phase 2. compromise sk.",
+        )
+        .unwrap();
+        assert_eq!(model.last_phase, Some(2));
+        let rules = model
+            .rules
+            .iter()
+            .map(|(rule, _)| rule.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rules,
+            vec![
+                "att_phase(Phase, X) => att_phase(Phase, pub(X))",
+                "att_phase(0, X) => att_phase(0, h(X))",
+                "att_phase(0, k) => ev(k)",
+                "att_phase(2, sk)",
+                "att_phase(0, X) => att_phase(1, X)",
+                "att_phase(1, X) => att_phase(2, X)",
+            ]
+        );
+    }
+
+    #[test]
+    fn forward_secrecy_test() {
+        let mut sniffer = Sniffer::new("examples/forward-secrecy.pif").unwrap();
+        let query = |source: &str, n| Parser::parse_query(source).unwrap().at_phase(n);
+        assert!(sniffer.find(&query("att(k1).", 0)).is_err());
+        assert!(sniffer.find(&query("att(k1).", 1)).is_ok());
+        assert!(sniffer.find(&query("att(k2[pub(ske)]).", 1)).is_err());
+
+        // Unscoped queries ask about the last phase
+        assert_eq!(sniffer.last_phase(), Some(1));
+        let unscoped = sniffer.in_last_phase(&Parser::parse_query("att(k1).").unwrap());
+        assert_eq!(unscoped, query("att(k1).", 1));
+    }
+}
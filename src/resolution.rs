@@ -1,11 +1,54 @@
 use crate::ast::{Atom, InnerRule, Rule};
 use crate::identifiers::{Identifier, IdentifierServer};
+use crate::unify::UnificationFailure;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+/// Represents the atom selected in a rule for resolution
+///
+/// Serialized as `{"kind": "premise", "atom": ..., "index": ...}`
+/// or `{"kind": "conclusion", "atom": ...}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(
+    from = "SelectionRepr<T>",
+    into = "SelectionRepr<T>",
+    bound = "T: Clone + Serialize + for<'a> Deserialize<'a>"
+)]
 pub enum Selection<T> {
     Premise(Atom<T>, usize),
     Conclusion(Atom<T>),
 }
+
+/// Serialization proxy for `Selection`, as tagged enums require named fields
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum SelectionRepr<T> {
+    Premise { atom: Atom<T>, index: usize },
+    Conclusion { atom: Atom<T> },
+}
+impl<T> From<SelectionRepr<T>> for Selection<T> {
+    fn from(value: SelectionRepr<T>) -> Self {
+        match value {
+            SelectionRepr::Premise { atom, index } => Selection::Premise(atom, index),
+            SelectionRepr::Conclusion { atom } => Selection::Conclusion(atom),
+        }
+    }
+}
+impl<T> From<Selection<T>> for SelectionRepr<T> {
+    fn from(value: Selection<T>) -> Self {
+        match value {
+            Selection::Premise(atom, index) => SelectionRepr::Premise { atom, index },
+            Selection::Conclusion(atom) => SelectionRepr::Conclusion { atom },
+        }
+    }
+}
+impl<T> Selection<T> {
+    /// Returns the selected atom
+    pub fn atom(&self) -> &Atom<T> {
+        match self {
+            Selection::Premise(a, _) | Selection::Conclusion(a) => a,
+        }
+    }
+}
 impl TryFrom<(&Selection<Identifier>, &IdentifierServer)> for Selection<String> {
     type Error = ();
     fn try_from(
@@ -18,39 +61,59 @@ impl TryFrom<(&Selection<Identifier>, &IdentifierServer)> for Selection<String>
     }
 }
 
+/// Reasons for two rules not to resolve
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResolutionFailure {
+    /// No rule has its conclusion selected while the other has a premise selected
+    Selection,
+    /// The selected atoms do not unify
+    Unification(UnificationFailure),
+    /// The constraints of the resolvent can never hold
+    Constraints,
+}
+
 impl InnerRule {
+    pub fn resolve(
+        &self,
+        other: &InnerRule,
+        select: impl Fn(&InnerRule) -> Selection<Identifier>,
+    ) -> Option<InnerRule> {
+        self.try_resolve(other, select).ok()
+    }
+
     /// Resolution of r1 and r2
     /// r1 = |p| /\ q => r  (selected p)
     /// r2 = s /\ t => |c|  (selected c)
     /// if unfify(p, c) {
-    ///     (q /\ s /\ t => r).asssigned(unify_context)
-    ///     - delete every Att(X) from q /\ s /\ t where X is not in r
-    ///     return (q /\ s /\ t => r)
+    ///     return (q /\ s /\ t => r).asssigned(unify_context)
     /// }
-    pub fn resolve(
+    /// The constraints of both rules are gathered and simplified, while the resolvent is left
+    /// to be simplified otherwise (see the `simplify` module)
+    pub fn try_resolve(
         &self,
         other: &InnerRule,
         select: impl Fn(&InnerRule) -> Selection<Identifier>,
-        keep: impl Fn(&Atom<Identifier>, &Atom<Identifier>) -> bool,
-    ) -> Option<InnerRule> {
+    ) -> Result<InnerRule, ResolutionFailure> {
         match (select(self), select(other)) {
-            (Selection::Premise(p, i), Selection::Conclusion(c)) => p.unify(&c).map(|bindings| {
+            (Selection::Premise(p, i), Selection::Conclusion(c)) => {
+                let bindings = p.try_unify(&c).map_err(ResolutionFailure::Unification)?;
                 let mut premises = self.premises.clone();
                 premises.remove(i);
                 premises.append(&mut other.premises.clone());
-                let mut rule = Rule {
+                let mut constraints = self.constraints.clone();
+                constraints.append(&mut other.constraints.clone());
+                Rule {
                     conclusion: self.conclusion.clone(),
                     premises,
-                };
-                rule = rule.apply(&bindings);
-                rule.premises.retain(|p| keep(p, &rule.conclusion));
-                rule
-            }),
-            (Selection::Conclusion(_), Selection::Premise(_, _)) => {
-                other.resolve(self, select, keep)
+                    constraints,
+                }
+                .apply(&bindings)
+                .simplify_constraints()
+                .ok_or(ResolutionFailure::Constraints)
             }
+            (Selection::Conclusion(_), Selection::Premise(_, _)) => other.try_resolve(self, select),
 
-            _ => None,
+            _ => Err(ResolutionFailure::Selection),
         }
     }
 }
@@ -0,0 +1,134 @@
+//! Simplify module
+//! Simplifications applied to every resolvent, in order:
+//! 1. tautology deletion: a rule whose conclusion is one of its premises derives nothing new
+//! 2. factoring: a premise appearing several times only needs to be derived once
+//! 3. unconstrained premises: the attacker knows at least one message, so a premise `att(X)`
+//!    is always satisfiable when `X` occurs nowhere else in the rule. In phased models, knowledge
+//!    carrying forward, premises `att_phase(n, X)` are as well when `X` only occurs in such ones
+use crate::ast::{InnerAtom, InnerRule, Rule};
+use crate::attack::Attacker;
+use rustc_hash::FxHashSet;
+
+pub struct Simplifier {
+    attacker: Attacker,
+}
+impl Simplifier {
+    pub fn new(attacker: Attacker) -> Simplifier {
+        Simplifier { attacker }
+    }
+
+    /// Simplifies a resolvent, returning `None` if it is a tautology, along with the indices
+    /// of the premises removed otherwise
+    pub fn simplify(&self, rule: InnerRule) -> Option<(InnerRule, Vec<usize>)> {
+        if Simplifier::is_tautology(&rule) {
+            return None;
+        }
+        Some(self.reduce(rule))
+    }
+
+    /// Checks if the conclusion of `rule` is one of its premises
+    pub fn is_tautology(rule: &InnerRule) -> bool {
+        rule.premises.contains(&rule.conclusion)
+    }
+
+    /// Factors the premises of `rule`, then removes its unconstrained ones. Returns the indices
+    /// of the premises removed, in increasing order
+    pub fn reduce(&self, rule: InnerRule) -> (InnerRule, Vec<usize>) {
+        let mut seen = FxHashSet::default();
+        let (premises, mut removed): (Vec<_>, Vec<_>) = rule
+            .premises
+            .into_iter()
+            .enumerate()
+            .partition(|(_, p)| seen.insert(p.clone()));
+
+        let factored = Rule {
+            premises: premises.iter().map(|(_, p)| p.clone()).collect(),
+            ..rule
+        };
+        let (premises, unconstrained): (Vec<_>, Vec<_>) = premises
+            .into_iter()
+            .partition(|(_, p)| !self.is_unconstrained(p, &factored));
+        removed.extend(unconstrained);
+        let mut removed = removed.into_iter().map(|(i, _)| i).collect::<Vec<_>>();
+        removed.sort_unstable();
+
+        let rule = Rule {
+            premises: premises.into_iter().map(|(_, p)| p).collect(),
+            ..factored
+        };
+        (rule, removed)
+    }
+
+    /// Checks if `premise` is `att(X)`, or `att_phase(n, X)`, with `X` occurring nowhere else
+    /// in the factored `rule` than in such premises
+    fn is_unconstrained(&self, premise: &InnerAtom, rule: &InnerRule) -> bool {
+        let Some(x) = self.attacker.message(premise).filter(|m| m.is_variable()) else {
+            return false;
+        };
+        !rule.conclusion.contains_variable(x)
+            && !rule.constraints.iter().any(|c| c.contains_variable(x))
+            && rule
+                .premises
+                .iter()
+                .filter(|p| p.contains_variable(x))
+                .all(|p| {
+                    self.attacker.message(p) == Some(x)
+                        && p.parameters
+                            .iter()
+                            .filter(|t| t.contains_variable(x))
+                            .count()
+                            == 1
+                })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identifiers::IdentifierServer;
+    use crate::Parser;
+
+    fn simplify(source: &str) -> Option<String> {
+        let mut id_server = IdentifierServer::default();
+        let attacker = Attacker::new(&mut id_server);
+        let rule = Parser::parse_rules(source).unwrap()[0].to_inner(&mut id_server);
+        Simplifier::new(attacker)
+            .simplify(rule)
+            .map(|(r, _)| r.to_string(&id_server).to_string())
+    }
+
+    #[test]
+    fn tautology_test() {
+        assert_eq!(
+            simplify("att(a) /\\ att(pair(a, b)) /\\ att(b) => att(pair(a, b))."),
+            None
+        );
+    }
+
+    #[test]
+    fn factoring_test() {
+        assert_eq!(
+            simplify("att(a) /\\ att(X) /\\ att(a) => att(pair(X, a))."),
+            Some("att(a) /\\ att(VAR0) => att(pair(VAR0, a))".to_string())
+        );
+    }
+
+    #[test]
+    fn unconstrained_premise_test() {
+        // `att(X)` is removed once factored, `att(Y)` is constrained by `att(pair(Y, a))`
+        assert_eq!(
+            simplify("att(X) /\\ att(X) /\\ att(Y) /\\ ev(Z) /\\ att(pair(Y, a)) => att(a)."),
+            Some("att(VAR1) /\\ ev(VAR2) /\\ att(pair(VAR1, a)) => att(a)".to_string())
+        );
+    }
+
+    #[test]
+    fn removed_premises_test() {
+        let mut id_server = IdentifierServer::default();
+        let attacker = Attacker::new(&mut id_server);
+        let source = "att(Y) /\\ att(X) /\\ att(pair(Y, a)) /\\ att(Y) /\\ att(X) => att(a).";
+        let rule = Parser::parse_rules(source).unwrap()[0].to_inner(&mut id_server);
+        let (_, removed) = Simplifier::new(attacker).reduce(rule);
+        assert_eq!(removed, vec![1, 3, 4]);
+    }
+}
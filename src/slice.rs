@@ -0,0 +1,204 @@
+//! Slice module
+//! Slicing keeps the input rules which may take part in deriving a query: those concluding an
+//! atom that unifies with the query, or with a premise of a rule kept already. The other rules
+//! can never help deriving it, so the saturation of a query leaves them out
+use crate::ast::{Atom, InnerAtom, InnerRule, Rule};
+use crate::derivation_tree::Origin;
+use crate::Sniffer;
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+/// Reasons for a rule to be pruned from the slice of a query
+#[derive(Debug, Clone)]
+pub enum PruneReason {
+    /// Neither the query nor the premises of kept rules have the predicate of its conclusion
+    UnusedPredicate,
+    /// Its conclusion unifies with none of the atoms of its predicate the slice needs, listed
+    NotUnifiable(Vec<Atom<String>>),
+}
+
+/// Input rules kept to derive a query, and those pruned along with the reason why
+#[derive(Debug, Clone)]
+pub struct Slice {
+    pub query: Atom<String>,
+    pub kept: Vec<Rule<String>>,
+    pub pruned: Vec<(Rule<String>, PruneReason)>,
+}
+
+impl Sniffer {
+    /// Reports the input rules which cannot take part in deriving `atom`, which the saturation
+    /// of a query about it leaves out, along with why
+    pub fn slice(&mut self, atom: &Atom<String>) -> Slice {
+        let query = atom.to_inner(&mut self.id_server, &mut HashMap::new());
+        let inputs = self
+            .input_rules
+            .iter()
+            .sorted_by_key(|(r, origin)| match origin {
+                Origin::Input { line, .. } => (*line, r.to_string(&self.id_server).to_string()),
+                _ => (usize::MAX, r.to_string(&self.id_server).to_string()),
+            })
+            .map(|(r, _)| r.clone())
+            .collect_vec();
+        let (kept, needed) = self.slice_of(query, &inputs);
+
+        let mut slice = Slice {
+            query: atom.clone(),
+            kept: vec![],
+            pruned: vec![],
+        };
+        for (rule, kept) in inputs.iter().zip(kept) {
+            if kept {
+                slice.kept.push(rule.to_string(&self.id_server));
+                continue;
+            }
+            let same_predicate = needed
+                .iter()
+                .filter(|a| a.symbol == rule.conclusion.symbol)
+                .map(|a| a.to_string(&self.id_server))
+                .unique_by(|a| a.to_string())
+                .collect_vec();
+            let reason = if same_predicate.is_empty() {
+                PruneReason::UnusedPredicate
+            } else {
+                PruneReason::NotUnifiable(same_predicate)
+            };
+            slice.pruned.push((rule.to_string(&self.id_server), reason));
+        }
+        slice
+    }
+
+    /// Input rules which may take part in deriving `query`, those a saturation about it starts from
+    pub(crate) fn sliced_inputs(&self, query: &InnerAtom) -> Vec<InnerRule> {
+        let inputs = self.input_rules.keys().cloned().collect_vec();
+        let (kept, _) = self.slice_of(query.clone(), &inputs);
+        inputs
+            .into_iter()
+            .zip(kept)
+            .filter_map(|(rule, kept)| kept.then_some(rule))
+            .collect()
+    }
+
+    /// Tells which of `inputs` are kept to derive `query`, along with the atoms the slice needs
+    fn slice_of(&self, query: InnerAtom, inputs: &[InnerRule]) -> (Vec<bool>, Vec<InnerAtom>) {
+        // Conclusions are renamed apart from the premises, which recursive rules share them with
+        let variables = self.id_server.variables().scratch();
+        let conclusions = inputs
+            .iter()
+            .map(|r| r.make_fresh(&variables).conclusion)
+            .collect_vec();
+
+        let mut needed: Vec<InnerAtom> = vec![query];
+        let mut kept = vec![false; inputs.len()];
+        let mut next = 0;
+        while let Some(goal) = needed.get(next).cloned() {
+            next += 1;
+            for (i, conclusion) in conclusions.iter().enumerate() {
+                if !kept[i] && goal.unify(conclusion).is_some() {
+                    kept[i] = true;
+                    needed.extend(inputs[i].premises.iter().cloned())
+                }
+            }
+        }
+        (kept, needed)
+    }
+}
+
+impl Display for Slice {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "kept {} rules to derive {}, pruned {}",
+            self.kept.len(),
+            self.query,
+            self.pruned.len()
+        )?;
+        for (rule, reason) in &self.pruned {
+            writeln!(f, "  {rule}")?;
+            match reason {
+                PruneReason::UnusedPredicate => writeln!(
+                    f,
+                    "    no atom the slice needs has predicate `{}`",
+                    rule.conclusion.symbol
+                )?,
+                PruneReason::NotUnifiable(atoms) => writeln!(
+                    f,
+                    "    its conclusion unifies with none of the atoms the slice needs: {}",
+                    atoms.iter().join(", ")
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+    use std::str::FromStr;
+
+    #[test]
+    fn prune_test() {
+        let mut sniffer = Sniffer::from_str(
+            "att(senc(X, Y)) /\\ att(Y) => att(X). att(senc(s, k)). att(k).
+            att(s) => ev(s). att(k) => ev(t). ev(X) => log(X).",
+        )
+        .unwrap();
+        let slice = sniffer.slice(&Parser::parse_query("ev(s).").unwrap());
+        assert_eq!(slice.kept.len(), 4);
+        assert_eq!(sniffer.rules.len(), 6);
+
+        let reasons = slice
+            .pruned
+            .iter()
+            .map(|(rule, reason)| (rule.conclusion.to_string(), reason))
+            .collect_vec();
+        assert!(matches!(
+            &reasons[..],
+            [
+                (t, PruneReason::NotUnifiable(atoms)),
+                (log, PruneReason::UnusedPredicate),
+            ] if t == "ev(t)" && atoms.len() == 1 && log.starts_with("log")
+        ));
+    }
+
+    #[test]
+    fn query_test() {
+        let mut sniffer = Sniffer::new("examples/nspk.pif").unwrap();
+        let query = Parser::parse_query("att(nb[na[ski]]).").unwrap();
+        let slice = sniffer.slice(&query);
+        assert!(slice
+            .pruned
+            .iter()
+            .all(|(r, _)| r.conclusion.symbol == "event"));
+        assert!(!slice.pruned.is_empty());
+        assert!(sniffer.find(&query).is_ok());
+    }
+
+    #[test]
+    fn sliced_saturation_test() {
+        let model =
+            "att(k). att(k) => att(s). att(s) => ev(s). ev(s) => att(log(s)). att(u) => ev(u).";
+        let mut sniffer = Sniffer::from_str(model).unwrap();
+        let event = Parser::parse_query("ev(s).").unwrap();
+        assert!(sniffer.find(&event).is_ok());
+
+        // Slicing reports without pruning anything
+        let slice = sniffer.slice(&Parser::parse_query("att(t).").unwrap());
+        assert_eq!(slice.pruned.len(), 5);
+        assert!(sniffer.find(&event).is_ok());
+
+        // Queries only saturate the rules of their slice, the ones of later queries being added
+        let mut sniffer = Sniffer::from_str(model).unwrap();
+        let inputs = |sniffer: &Sniffer| sniffer.saturations.values().next().unwrap().inputs.len();
+        assert!(sniffer
+            .find(&Parser::parse_query("att(s).").unwrap())
+            .is_ok());
+        assert_eq!(inputs(&sniffer), 2);
+        assert!(sniffer
+            .find(&Parser::parse_query("att(log(s)).").unwrap())
+            .is_ok());
+        assert_eq!(inputs(&sniffer), 4);
+    }
+}
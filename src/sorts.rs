@@ -0,0 +1,194 @@
+//! Sorts module
+//! Declarations give sorts to the arguments and result of function symbols, against which
+//! rules are checked. Undeclared symbols may be used with any sort, so that models without
+//! declarations are simply untyped
+use crate::ast::{Declaration, Rule, Term};
+use crate::derivation_tree::Origin;
+use crate::error::SemanticError;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+/// The sort of messages, which always exists
+pub const BITSTRING: &str = "bitstring";
+
+/// Declared sorts and function signatures of a model
+#[derive(Default, Debug, Clone)]
+pub struct Signature {
+    sorts: FxHashSet<String>,
+    functions: FxHashMap<String, (Vec<String>, String)>,
+}
+impl Signature {
+    /// Adds a declaration to the signature, which must only use previously declared sorts
+    pub fn declare(&mut self, declaration: &Declaration, origin: &Origin) -> Vec<SemanticError> {
+        let mut errors = vec![];
+        match declaration {
+            Declaration::Type(sort) => {
+                if sort == BITSTRING || !self.sorts.insert(sort.clone()) {
+                    errors.push(SemanticError::Redeclared {
+                        symbol: sort.clone(),
+                        origin: origin.clone(),
+                    })
+                }
+            }
+            Declaration::Function {
+                symbol,
+                parameters,
+                result,
+            } => {
+                for sort in parameters.iter().chain(std::iter::once(result)) {
+                    if !self.is_sort(sort) {
+                        errors.push(SemanticError::UnknownSort {
+                            sort: sort.clone(),
+                            origin: origin.clone(),
+                        })
+                    }
+                }
+                if self.functions.contains_key(symbol) {
+                    errors.push(SemanticError::Redeclared {
+                        symbol: symbol.clone(),
+                        origin: origin.clone(),
+                    })
+                } else if errors.is_empty() {
+                    self.functions
+                        .insert(symbol.clone(), (parameters.clone(), result.clone()));
+                }
+            }
+        }
+        errors
+    }
+
+    fn is_sort(&self, sort: &str) -> bool {
+        sort == BITSTRING || self.sorts.contains(sort)
+    }
+
+    /// Checks that the arguments of declared function symbols have the expected sorts, and that
+    /// each variable of the rule is only used with a single sort. Predicates accept any sort
+    pub fn check_rule(&self, rule: &Rule<String>, origin: &Origin) -> Vec<SemanticError> {
+        let mut variables = FxHashMap::default();
+        let mut errors = vec![];
+        for atom in rule
+            .premises
+            .iter()
+            .chain(std::iter::once(&rule.conclusion))
+        {
+            for t in &atom.parameters {
+                self.check_term(t, None, &mut variables, &mut errors, origin)
+            }
+        }
+        for c in &rule.constraints {
+            self.check_term(&c.left, None, &mut variables, &mut errors, origin);
+            self.check_term(&c.right, None, &mut variables, &mut errors, origin)
+        }
+        errors
+    }
+
+    fn check_term<'a>(
+        &'a self,
+        term: &'a Term<String>,
+        expected: Option<&'a str>,
+        variables: &mut FxHashMap<&'a str, &'a str>,
+        errors: &mut Vec<SemanticError>,
+        origin: &Origin,
+    ) {
+        let mismatch = |expected: &str, found: &str| SemanticError::SortMismatch {
+            term: term.to_string(),
+            expected: expected.to_string(),
+            found: found.to_string(),
+            origin: origin.clone(),
+        };
+
+        match term {
+            Term::Variable { symbol } => {
+                if let Some(expected) = expected {
+                    let sort = *variables.entry(symbol).or_insert(expected);
+                    if sort != expected {
+                        errors.push(mismatch(expected, sort))
+                    }
+                }
+            }
+            Term::Function { symbol, parameters } => match self.functions.get(symbol) {
+                Some((sorts, result)) => {
+                    if let Some(expected) = expected.filter(|e| e != result) {
+                        errors.push(mismatch(expected, result))
+                    }
+                    for (t, sort) in parameters.iter().zip(sorts) {
+                        self.check_term(t, Some(sort), variables, errors, origin)
+                    }
+                }
+                None => {
+                    for t in parameters {
+                        self.check_term(t, None, variables, errors, origin)
+                    }
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    fn check(source: &str) -> Vec<SemanticError> {
+        let model = Parser::parse_model(source).unwrap();
+        let mut signature = Signature::default();
+        let mut errors = vec![];
+        for (declaration, _) in &model.declarations {
+            errors.extend(signature.declare(declaration, &Origin::Added));
+        }
+        for (rule, _) in &model.rules {
+            errors.extend(signature.check_rule(rule, &Origin::Added));
+        }
+        errors
+    }
+
+    const SIGNATURE: &str = "type key. fun senc(bitstring, key): bitstring. fun k: key.";
+
+    #[test]
+    fn well_sorted_test() {
+        let errors = check(&format!(
+            "{SIGNATURE} att(senc(X, Y)) /\\ att(Y) => att(X). att(senc(pair(a, b), k))."
+        ));
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn sort_mismatch_test() {
+        let errors = check(&format!("{SIGNATURE} att(senc(k, senc(a, k)))."));
+        assert_eq!(
+            errors,
+            vec![
+                SemanticError::SortMismatch {
+                    term: "k".to_string(),
+                    expected: BITSTRING.to_string(),
+                    found: "key".to_string(),
+                    origin: Origin::Added,
+                },
+                SemanticError::SortMismatch {
+                    term: "senc(a, k)".to_string(),
+                    expected: "key".to_string(),
+                    found: BITSTRING.to_string(),
+                    origin: Origin::Added,
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn variable_sort_mismatch_test() {
+        let errors = check(&format!("{SIGNATURE} att(senc(X, X)) => att(X)."));
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn unknown_sort_test() {
+        let errors = check("fun senc(bitstring, key): bitstring.");
+        assert_eq!(
+            errors,
+            vec![SemanticError::UnknownSort {
+                sort: "key".to_string(),
+                origin: Origin::Added,
+            }]
+        );
+    }
+}
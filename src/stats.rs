@@ -0,0 +1,156 @@
+//! Statistics module
+//! Counters and timings collected during saturation, to understand what it spends its time on
+use crate::ast::InnerRule;
+use crate::resolution::ResolutionFailure;
+use crate::unify::UnificationFailure;
+use itertools::Itertools;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+/// Statistics of the saturations done since they were last reset
+#[derive(Debug, Clone, Default)]
+pub struct Statistics {
+    /// Resolvents produced, whether they were kept or not
+    pub generated: usize,
+    /// Resolvents added to the rules to process
+    pub kept: usize,
+    /// Resolvents discarded as they were already processed
+    pub duplicates: usize,
+    /// Resolvents discarded as their conclusion is one of their premises
+    pub tautologies: usize,
+    /// Resolvents discarded as they exceed the bound
+    pub rejected: usize,
+    /// Resolvents approximated as they exceed the bound
+    pub approximated: usize,
+    /// Pairs of rules whose selected atoms were unified
+    pub resolutions_attempted: usize,
+    pub resolutions_successful: usize,
+    pub unification_failures: BTreeMap<UnificationFailure, usize>,
+    /// Resolutions with facts attempted while looking queries up, apart from those of saturation
+    pub lookup_resolutions_attempted: usize,
+    pub lookup_resolutions_successful: usize,
+    /// Resolutions whose resolvent had constraints which can never hold
+    pub unsatisfiable_constraints: usize,
+    /// Maximum depth of the terms of kept resolvents
+    pub max_term_depth: usize,
+    /// Kept resolvents, by predicate of their conclusion
+    pub predicates: BTreeMap<String, usize>,
+    /// Time spent saturating, resolution included
+    pub saturation_time: Duration,
+    /// Time spent resolving rules against each other
+    pub resolution_time: Duration,
+    /// Time spent looking queries up in a saturated rule set
+    pub lookup_time: Duration,
+    /// Time spent answering queries backward, without saturating
+    pub backward_time: Duration,
+    /// Time spent building derivation trees
+    pub derivation_time: Duration,
+}
+impl Statistics {
+    /// Records the outcome of the resolution of two rules
+    pub fn record_resolution(&mut self, result: &Result<InnerRule, ResolutionFailure>) {
+        match result {
+            Ok(_) => {
+                self.resolutions_attempted += 1;
+                self.resolutions_successful += 1
+            }
+            Err(ResolutionFailure::Unification(failure)) => {
+                self.resolutions_attempted += 1;
+                *self.unification_failures.entry(*failure).or_default() += 1
+            }
+            Err(ResolutionFailure::Constraints) => {
+                self.resolutions_attempted += 1;
+                self.unsatisfiable_constraints += 1
+            }
+            Err(ResolutionFailure::Selection) => (),
+        }
+    }
+
+    /// Records the outcome of the resolution of a rule with a fact while looking a query up
+    pub fn record_lookup_resolution(&mut self, result: &Result<InnerRule, ResolutionFailure>) {
+        match result {
+            Ok(_) => {
+                self.lookup_resolutions_attempted += 1;
+                self.lookup_resolutions_successful += 1
+            }
+            Err(ResolutionFailure::Selection) => (),
+            Err(_) => self.lookup_resolutions_attempted += 1,
+        }
+    }
+
+    /// Records a resolvent kept for further resolution, concluding the given predicate
+    pub fn record_kept(&mut self, rule: &InnerRule, predicate: String) {
+        self.kept += 1;
+        *self.predicates.entry(predicate).or_default() += 1;
+        let depth = rule
+            .premises
+            .iter()
+            .chain(std::iter::once(&rule.conclusion))
+            .flat_map(|a| &a.parameters)
+            .map(|t| t.depth())
+            .max()
+            .unwrap_or(0);
+        self.max_term_depth = self.max_term_depth.max(depth)
+    }
+}
+impl Display for Statistics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "rules: {} generated, {} kept, {} duplicates, {} tautologies",
+            self.generated, self.kept, self.duplicates, self.tautologies
+        )?;
+        if self.rejected > 0 || self.approximated > 0 {
+            writeln!(
+                f,
+                "bound: {} rejected, {} approximated",
+                self.rejected, self.approximated
+            )?;
+        }
+        writeln!(
+            f,
+            "resolutions: {} attempted, {} successful",
+            self.resolutions_attempted, self.resolutions_successful
+        )?;
+        if self.lookup_resolutions_attempted > 0 {
+            writeln!(
+                f,
+                "lookup resolutions: {} attempted, {} successful",
+                self.lookup_resolutions_attempted, self.lookup_resolutions_successful
+            )?;
+        }
+        if !self.unification_failures.is_empty() {
+            let failures = self
+                .unification_failures
+                .iter()
+                .map(|(reason, count)| format!("{count} {reason}"))
+                .join(", ");
+            writeln!(f, "unification failures: {failures}")?;
+        }
+        if self.unsatisfiable_constraints > 0 {
+            writeln!(
+                f,
+                "unsatisfiable constraints: {}",
+                self.unsatisfiable_constraints
+            )?;
+        }
+        writeln!(f, "max term depth: {}", self.max_term_depth)?;
+        writeln!(
+            f,
+            "time: {:?} saturating ({:?} resolving), {:?} looking up, {:?} answering backward, {:?} building derivations",
+            self.saturation_time,
+            self.resolution_time,
+            self.lookup_time,
+            self.backward_time,
+            self.derivation_time
+        )?;
+        if !self.predicates.is_empty() {
+            writeln!(f, "kept rules by predicate:")?;
+            for (predicate, count) in &self.predicates {
+                writeln!(f, "  {predicate}: {count}")?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,180 @@
+//! Trace module
+//! Events reported while saturating, to follow what the saturation loop does as it runs
+use crate::ast::Rule;
+use crate::resolution::Selection;
+use std::fmt::{Display, Formatter};
+
+/// Detail of the events reported by a tracer, each level including the previous ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Rules picked to be resolved against the processed ones
+    Selections,
+    /// Resolvents kept for further resolution
+    Resolvents,
+    /// Resolvents discarded
+    Discarded,
+}
+
+/// Reasons for a resolvent to be discarded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiscardReason {
+    /// Its conclusion is one of its premises
+    Tautology,
+    /// It is one of its parents, or was already processed
+    Duplicate,
+    /// One of its terms exceeds the bound
+    OutOfBound,
+}
+
+/// Something that happened during saturation
+#[derive(Debug, Clone)]
+pub enum SaturationEvent {
+    /// A rule was picked to be resolved against the processed ones
+    Selected {
+        rule: Rule<String>,
+        selection: Selection<String>,
+    },
+    /// A resolvent was kept for further resolution
+    Resolved {
+        rule: Rule<String>,
+        parents: (Rule<String>, Rule<String>),
+    },
+    /// A resolvent was discarded
+    Discarded {
+        rule: Rule<String>,
+        reason: DiscardReason,
+    },
+}
+impl SaturationEvent {
+    /// Returns the rule the event is about
+    pub fn rule(&self) -> &Rule<String> {
+        match self {
+            SaturationEvent::Selected { rule, .. }
+            | SaturationEvent::Resolved { rule, .. }
+            | SaturationEvent::Discarded { rule, .. } => rule,
+        }
+    }
+}
+impl Display for SaturationEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaturationEvent::Selected { rule, selection } => {
+                write!(f, "select  {rule}  [{}]", selection.atom())
+            }
+            SaturationEvent::Resolved {
+                rule,
+                parents: (r1, r2),
+            } => write!(f, "resolve {rule}  from {r1}  and {r2}"),
+            SaturationEvent::Discarded { rule, reason } => {
+                let reason = match reason {
+                    DiscardReason::Tautology => "tautology",
+                    DiscardReason::Duplicate => "duplicate",
+                    DiscardReason::OutOfBound => "out of bound",
+                };
+                write!(f, "discard {rule}  ({reason})")
+            }
+        }
+    }
+}
+
+/// Observes the saturation events up to some verbosity, about rules concluding some predicates
+pub struct Tracer {
+    verbosity: Verbosity,
+    predicates: Vec<String>,
+    observer: Box<dyn FnMut(&SaturationEvent) + Send>,
+}
+impl Tracer {
+    /// Creates a tracer calling `observer` on every event up to the given verbosity
+    pub fn new(
+        verbosity: Verbosity,
+        observer: impl FnMut(&SaturationEvent) + Send + 'static,
+    ) -> Tracer {
+        Tracer {
+            verbosity,
+            predicates: vec![],
+            observer: Box::new(observer),
+        }
+    }
+
+    /// Only reports the events about rules concluding one of the given predicates, or all
+    /// of them if none is given
+    pub fn with_predicates(mut self, predicates: Vec<String>) -> Tracer {
+        self.predicates = predicates;
+        self
+    }
+
+    /// Checks whether events of the given level, about rules concluding `predicate`, are reported
+    pub fn accepts(&self, level: Verbosity, predicate: &str) -> bool {
+        level <= self.verbosity
+            && (self.predicates.is_empty() || self.predicates.iter().any(|p| p == predicate))
+    }
+
+    pub fn notify(&mut self, event: &SaturationEvent) {
+        (self.observer)(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sniffer;
+    use std::str::FromStr;
+    use std::sync::{Arc, Mutex};
+
+    fn trace(
+        tracer: impl FnOnce(Arc<Mutex<Vec<SaturationEvent>>>) -> Tracer,
+    ) -> Vec<SaturationEvent> {
+        let events = Arc::new(Mutex::new(vec![]));
+        let mut sniffer = Sniffer::from_str(
+            "att(senc(X, Y)) /\\ att(Y) => att(X). att(senc(s, k)). att(k). att(s) => ev(s).",
+        )
+        .unwrap();
+        sniffer.set_tracer(Some(tracer(events.clone())));
+        sniffer.saturate();
+        let events = std::mem::take(&mut *events.lock().unwrap());
+        events
+    }
+
+    #[test]
+    fn verbosity_test() {
+        let selections = trace(|events| {
+            Tracer::new(Verbosity::Selections, move |e| {
+                events.lock().unwrap().push(e.clone())
+            })
+        });
+        assert!(!selections.is_empty());
+        assert!(selections
+            .iter()
+            .all(|e| matches!(e, SaturationEvent::Selected { .. })));
+
+        let all = trace(|events| {
+            Tracer::new(Verbosity::Discarded, move |e| {
+                events.lock().unwrap().push(e.clone())
+            })
+        });
+        assert!(all
+            .iter()
+            .any(|e| matches!(e, SaturationEvent::Resolved { .. })));
+        assert!(all.len() > selections.len());
+    }
+
+    #[test]
+    fn predicate_filter_test() {
+        let events = trace(|events| {
+            Tracer::new(Verbosity::Discarded, move |e| {
+                events.lock().unwrap().push(e.clone())
+            })
+            .with_predicates(vec!["ev".to_string()])
+        });
+        assert!(!events.is_empty());
+        assert!(events.iter().all(|e| e.rule().conclusion.symbol == "ev"));
+    }
+
+    #[test]
+    fn traced_sniffer_is_send_test() {
+        fn assert_send<T: Send>(_: &T) {}
+        let mut sniffer = Sniffer::from_str("att(k).").unwrap();
+        sniffer.set_tracer(Some(Tracer::new(Verbosity::Discarded, |_| {})));
+        assert_send(&sniffer);
+    }
+}
@@ -3,25 +3,41 @@ use crate::identifiers::Identifier;
 use crate::union_find::UnionFind;
 use rustc_hash::FxHashMap;
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+/// Reasons for two terms not to unify
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum UnificationFailure {
+    /// Two different function symbols have to be equal
+    SymbolClash,
+    /// A function symbol is given different numbers of arguments
+    ArityMismatch,
+    /// A variable has to be equal to a term containing it
+    OccursCheck,
+}
+impl Display for UnificationFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnificationFailure::SymbolClash => write!(f, "symbol clash"),
+            UnificationFailure::ArityMismatch => write!(f, "arity mismatch"),
+            UnificationFailure::OccursCheck => write!(f, "occurs check"),
+        }
+    }
+}
 
 struct VarInfo {
-    pub marker: usize,
     pub bound: Option<InnerTerm>,
 }
 
 #[derive(Default)]
 pub struct UnificationGraph {
-    marker: usize,
     nodes: FxHashMap<Identifier, VarInfo>,
     equivalence_classes: UnionFind<InnerTerm>,
 }
 impl UnificationGraph {
     /// Returns `false` if the symbol is already bound
     pub fn bind(&mut self, symbol: Identifier, term: InnerTerm) -> bool {
-        let node = self.nodes.entry(symbol).or_insert(VarInfo {
-            marker: self.marker,
-            bound: None,
-        });
+        let node = self.nodes.entry(symbol).or_insert(VarInfo { bound: None });
 
         if node.bound.is_none() {
             node.bound = Some(term);
@@ -35,25 +51,6 @@ impl UnificationGraph {
         self.equivalence_classes.union(x, y);
     }
 
-    pub fn incr_marker(&mut self) {
-        self.marker += 1
-    }
-
-    /// Returns `true` if the term was not already visited
-    pub fn visit(&mut self, symbol: Identifier) -> bool {
-        let node = self.nodes.entry(symbol).or_insert(VarInfo {
-            marker: self.marker + 1,
-            bound: None,
-        });
-
-        if node.marker != self.marker {
-            node.marker = self.marker;
-            true
-        } else {
-            false
-        }
-    }
-
     pub fn deref_mut(&mut self, term: InnerTerm) -> InnerTerm {
         let root = self.equivalence_classes.find_equivalence_mut(term);
         if let Some(VarInfo { bound: Some(t), .. }) = self.nodes.get(root.symbol()) {
@@ -76,26 +73,54 @@ impl UnificationGraph {
         }
     }
 
+    /// Returns the most general unifier, whose bindings can be applied at once
     pub fn bindings(mut self) -> FxHashMap<InnerTerm, InnerTerm> {
         let mut bindings = HashMap::default();
         for t in self.equivalence_classes.clone().iter() {
             bindings.insert(t.clone(), self.deref_mut(t.clone()));
         }
+        // Bound terms may contain variables which are bound themselves
         bindings
+            .iter()
+            .map(|(t, u)| (t.clone(), u.apply_fully(&bindings)))
+            .collect()
     }
 }
 
 impl InnerAtom {
     pub fn unify(&self, other: &InnerAtom) -> Option<FxHashMap<InnerTerm, InnerTerm>> {
-        Term::from(self.clone()).unify(&Term::from(other.clone()))
+        self.try_unify(other).ok()
+    }
+
+    /// Unifies this atom with another, telling why it failed if it did
+    pub fn try_unify(
+        &self,
+        other: &InnerAtom,
+    ) -> Result<FxHashMap<InnerTerm, InnerTerm>, UnificationFailure> {
+        Term::from(self.clone()).try_unify(&Term::from(other.clone()))
     }
 }
 
 impl InnerTerm {
     /// Tries to unify this term with another
     pub fn unify(&self, other: &InnerTerm) -> Option<FxHashMap<InnerTerm, InnerTerm>> {
+        self.try_unify(other).ok()
+    }
+
+    /// Unifies this term with another, telling why it failed if it did
+    pub fn try_unify(
+        &self,
+        other: &InnerTerm,
+    ) -> Result<FxHashMap<InnerTerm, InnerTerm>, UnificationFailure> {
+        InnerTerm::try_unify_all(vec![(self.clone(), other.clone())])
+    }
+
+    /// Finds a single unifier for every pair of terms, telling why it failed if it did
+    pub fn try_unify_all(
+        pairs: Vec<(InnerTerm, InnerTerm)>,
+    ) -> Result<FxHashMap<InnerTerm, InnerTerm>, UnificationFailure> {
         let mut context = UnificationGraph::default();
-        let mut to_visit = vec![(self.clone(), other.clone())];
+        let mut to_visit = pairs;
 
         while let Some((t, u)) = to_visit.pop() {
             // Finds leaves of terms `self` and `other`
@@ -114,7 +139,7 @@ impl InnerTerm {
                 (x @ Term::Variable { symbol: x_id }, f @ Term::Function { .. })
                 | (f @ Term::Function { .. }, x @ Term::Variable { symbol: x_id }) => {
                     if f.contains(x, &mut context) {
-                        return None;
+                        return Err(UnificationFailure::OccursCheck);
                     } else {
                         context.bind(*x_id, f.clone());
                     }
@@ -129,41 +154,35 @@ impl InnerTerm {
                         parameters: g_params,
                     },
                 ) => {
-                    if f == g && f_params.len() == g_params.len() {
-                        context.union(t.clone(), u.clone());
-                        for unify in f_params.clone().into_iter().zip(g_params.clone()) {
-                            to_visit.push(unify)
-                        }
-                    } else {
-                        return None;
+                    if f != g {
+                        return Err(UnificationFailure::SymbolClash);
+                    } else if f_params.len() != g_params.len() {
+                        return Err(UnificationFailure::ArityMismatch);
+                    }
+                    context.union(t.clone(), u.clone());
+                    for unify in f_params.clone().into_iter().zip(g_params.clone()) {
+                        to_visit.push(unify)
                     }
                 }
             }
         }
 
-        Some(context.bindings())
+        Ok(context.bindings())
     }
 
-    /// Checks if this term contains variable `t`
+    /// Checks if this term contains variable `u`, once the variables bound in `context` are replaced
     pub fn contains(&self, u: &InnerTerm, context: &mut UnificationGraph) -> bool {
-        context.incr_marker();
-
         // Sadly Rust does not guarantee tail call optimizations (c.f https://dev.to/seanchen1991/the-story-of-tail-call-optimizations-in-rust-35hf)
         // Therefore, we must optimize this by hand
-        let mut to_visit = vec![self];
+        let mut to_visit = vec![self.clone()];
         while let Some(t) = to_visit.pop() {
-            if t == u {
+            let t = context.deref_mut(t);
+            if &t == u {
                 return true;
             }
 
-            if let Term::Function { symbol, parameters } = t {
-                if context.visit(*symbol) {
-                    for param in parameters {
-                        to_visit.push(param)
-                    }
-                } else {
-                    return false;
-                }
+            if let Term::Function { parameters, .. } = t {
+                to_visit.extend(parameters)
             }
         }
 
@@ -430,6 +449,34 @@ mod tests {
         assert!(unary_fun.unify(&binary_fun).is_none());
     }
 
+    #[test]
+    fn try_unify_failure_test() {
+        let x = Term::Variable {
+            symbol: Identifier::Variable(0),
+        };
+        let fun = |symbol, parameters| Term::Function {
+            symbol: Identifier::Function(symbol),
+            parameters,
+        };
+
+        assert_eq!(
+            fun(0, vec![x.clone()]).try_unify(&fun(1, vec![x.clone()])),
+            Err(UnificationFailure::SymbolClash)
+        );
+        assert_eq!(
+            fun(0, vec![x.clone()]).try_unify(&fun(0, vec![x.clone(), x.clone()])),
+            Err(UnificationFailure::ArityMismatch)
+        );
+        assert_eq!(
+            x.try_unify(&fun(0, vec![x.clone()])),
+            Err(UnificationFailure::OccursCheck)
+        );
+        let Ok(sigma) = x.try_unify(&fun(1, vec![])) else {
+            panic!("Expected X to unify with a constant")
+        };
+        assert_eq!(x.apply_fully(&sigma), fun(1, vec![]));
+    }
+
     #[test]
     fn unify_inner_function_test() {
         let nested_fun = Term::Function {
@@ -463,6 +510,81 @@ mod tests {
         );
     }
 
+    #[test]
+    fn unify_idempotent_bindings_test() {
+        let x = Term::Variable {
+            symbol: Identifier::Variable(0),
+        };
+        let y = Term::Variable {
+            symbol: Identifier::Variable(1),
+        };
+        let a = Term::Function {
+            symbol: Identifier::Function(2),
+            parameters: vec![],
+        };
+        let f = |t: InnerTerm| Term::Function {
+            symbol: Identifier::Function(1),
+            parameters: vec![t],
+        };
+        let pair = |t: InnerTerm, u: InnerTerm| Term::Function {
+            symbol: Identifier::Function(0),
+            parameters: vec![t, u],
+        };
+
+        let bindings = pair(x.clone(), y.clone()).unify(&pair(f(y), a.clone()));
+        assert!(bindings.is_some());
+        assert_eq!(bindings.unwrap().get(&x), Some(&f(a)));
+    }
+
+    #[test]
+    fn unify_occurs_through_bindings_test() {
+        let x = Term::Variable {
+            symbol: Identifier::Variable(0),
+        };
+        let y = Term::Variable {
+            symbol: Identifier::Variable(1),
+        };
+        let f = |t: InnerTerm| Term::Function {
+            symbol: Identifier::Function(1),
+            parameters: vec![t],
+        };
+        let pair = |t: InnerTerm, u: InnerTerm| Term::Function {
+            symbol: Identifier::Function(0),
+            parameters: vec![t, u],
+        };
+
+        assert_eq!(
+            pair(x.clone(), y.clone()).try_unify(&pair(f(y), f(x.clone()))),
+            Err(UnificationFailure::OccursCheck)
+        );
+        assert_eq!(
+            f(x.clone()).try_unify(&pair(x.clone(), x)),
+            Err(UnificationFailure::SymbolClash)
+        );
+    }
+
+    #[test]
+    fn unify_occurs_repeated_symbol_test() {
+        // Visiting `h` twice used to end the occurs check before reaching `X`
+        let x = Term::Variable {
+            symbol: Identifier::Variable(0),
+        };
+        let a = Term::Function {
+            symbol: Identifier::Function(2),
+            parameters: vec![],
+        };
+        let h = |t: InnerTerm| Term::Function {
+            symbol: Identifier::Function(1),
+            parameters: vec![t],
+        };
+        let pair = |t: InnerTerm, u: InnerTerm| Term::Function {
+            symbol: Identifier::Function(0),
+            parameters: vec![t, u],
+        };
+
+        assert!(x.unify(&pair(h(x.clone()), h(a))).is_none());
+    }
+
     // Hardcore optimization test, could burn your machine, handle with care
     #[ignore]
     #[test]
@@ -28,7 +28,7 @@ impl<T: Hash + Eq + PartialEq + Clone> Default for UnionFind<T> {
 }
 impl<T: Hash + Eq + PartialEq + Clone> UnionFind<T> {
     pub fn insert(&mut self, value: T) {
-        self.nodes.entry(value).or_insert(NodeInfo::default());
+        self.nodes.entry(value).or_default();
     }
 
     pub fn union(&mut self, x: T, y: T) {
@@ -0,0 +1,207 @@
+//! Why-not module
+//! Explains why a query is not derivable from a saturated rule set. Once saturated, every
+//! derivable atom is the conclusion of a rule whose conclusion is selected, so an atom
+//! unifying with none of those conclusions can never be derived. Saturations under a bound
+//! may have left derivations out, in which case the explanation is inconclusive
+use crate::ast::{Atom, InnerAtom, InnerRule, Rule};
+use crate::attack::Attacker;
+use crate::identifiers::Identifier;
+use crate::resolution::Selection;
+use crate::{global_selection, query_selection, SaturationFailure, Sniffer, Strategy};
+use itertools::Itertools;
+use rustc_hash::FxHashSet;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+
+/// Explanation of why a query is not derivable
+#[derive(Debug, Clone)]
+pub struct WhyNot {
+    pub query: Atom<String>,
+    /// Rules whose conclusion unifies with the query, instantiated accordingly, along with
+    /// their premises that were not derived. Rules needing the query itself are left out
+    pub candidates: Vec<(Rule<String>, Vec<Atom<String>>)>,
+    /// Saturated rules concluding the predicates the query depends upon
+    pub relevant: Vec<Rule<String>>,
+    /// Whether the saturation was bounded, so that the query and the premises that were not
+    /// derived may be derivable all the same
+    pub inconclusive: bool,
+}
+
+impl Sniffer {
+    /// Saturates the rule set for the given query, explaining why it is not derivable,
+    /// or returns `None` if it is
+    pub fn why_not(&mut self, atom: &Atom<String>) -> Option<WhyNot> {
+        let query = atom.to_inner(&mut self.id_server, &mut HashMap::new());
+        let attacker = Attacker::new(&mut self.id_server);
+        let strategy = self.strategy_of(&query, attacker);
+        let failure = self.find(atom).err()?;
+        let inconclusive = matches!(failure, SaturationFailure::Incomplete) || self.bound.is_some();
+
+        let processed = &self.saturations[&strategy].processed;
+        let solved = match strategy {
+            Strategy::Global => solved_rules(processed, global_selection(attacker)),
+            Strategy::Query(symbol) => solved_rules(processed, query_selection(symbol, attacker)),
+        };
+        let derivable = |p: &InnerAtom| solved.iter().any(|r| p.unify(&r.conclusion).is_some());
+
+        // Rules needing the query itself as a premise cannot help deriving it
+        let variables = self.id_server.variables().scratch();
+        let mut candidates = vec![];
+        for rule in processed.iter().sorted_by_key(|r| r.premises.len()) {
+            let rule = rule.make_fresh(&variables);
+            if let Some(bindings) = query.unify(&rule.conclusion) {
+                let instance = rule.apply_fully(&bindings);
+                if instance.premises.contains(&instance.conclusion) {
+                    continue;
+                }
+                let blocking = instance
+                    .premises
+                    .iter()
+                    .filter(|p| !derivable(p))
+                    .map(|p| p.to_string(&self.id_server))
+                    .unique()
+                    .collect();
+                candidates.push((instance.to_string(&self.id_server), blocking))
+            }
+        }
+        let candidates = candidates
+            .into_iter()
+            .unique_by(|(rule, _)| rule.to_string())
+            .collect();
+
+        let predicates = relevant_predicates(processed, query.symbol);
+        let relevant = processed
+            .iter()
+            .filter(|r| predicates.contains(&r.conclusion.symbol))
+            .map(|r| r.to_string(&self.id_server))
+            .sorted_by_key(|r| r.to_string())
+            .collect();
+
+        Some(WhyNot {
+            query: atom.clone(),
+            candidates,
+            relevant,
+            inconclusive,
+        })
+    }
+}
+
+/// Returns the rules whose conclusion is selected
+fn solved_rules(
+    rules: &FxHashSet<InnerRule>,
+    select: impl Fn(&InnerRule) -> Selection<Identifier>,
+) -> Vec<&InnerRule> {
+    rules
+        .iter()
+        .filter(|r| matches!(select(r), Selection::Conclusion(_)))
+        .collect()
+}
+
+/// Returns the predicates whose atoms may be used to derive atoms of the given predicate
+fn relevant_predicates(rules: &FxHashSet<InnerRule>, symbol: Identifier) -> FxHashSet<Identifier> {
+    let mut predicates = FxHashSet::from_iter([symbol]);
+    let mut pending = vec![symbol];
+    while let Some(predicate) = pending.pop() {
+        for p in rules
+            .iter()
+            .filter(|r| r.conclusion.symbol == predicate)
+            .flat_map(|r| &r.premises)
+        {
+            if predicates.insert(p.symbol) {
+                pending.push(p.symbol)
+            }
+        }
+    }
+    predicates
+}
+
+impl Display for WhyNot {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.inconclusive {
+            writeln!(
+                f,
+                "{} was not derived, but the saturation was bounded so it may be derivable",
+                self.query
+            )?;
+        } else {
+            writeln!(f, "{} is not derivable", self.query)?;
+        }
+        let blocked = if self.inconclusive {
+            "not derived"
+        } else {
+            "never derivable"
+        };
+        if self.candidates.is_empty() {
+            writeln!(f, "no rule concludes an atom unifying with it")?;
+        } else {
+            writeln!(f, "rules that could conclude it:")?;
+            for (rule, blocking) in &self.candidates {
+                writeln!(f, "  {rule}")?;
+                for premise in blocking {
+                    writeln!(f, "    {blocked}: {premise}")?;
+                }
+            }
+        }
+
+        writeln!(f, "saturated rules for the predicates it depends upon:")?;
+        for rule in &self.relevant {
+            writeln!(f, "  {rule}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Bound, Parser};
+    use std::str::FromStr;
+
+    #[test]
+    fn secret_why_not_test() {
+        let mut sniffer = Sniffer::from_str(
+            "att(senc(X, Y)) /\\ att(Y) => att(X). att(senc(secret, ksecret)). step(X) => att(pair(X, ksecret)).",
+        )
+        .unwrap();
+        let why_not = sniffer
+            .why_not(&Parser::parse_query("att(secret).").unwrap())
+            .unwrap();
+
+        let blocking = why_not
+            .candidates
+            .iter()
+            .flat_map(|(_, blocking)| blocking)
+            .collect_vec();
+        assert!(blocking.contains(&&Parser::parse_query("att(ksecret).").unwrap()));
+        assert!(!why_not.inconclusive);
+        assert!(why_not
+            .relevant
+            .iter()
+            .all(|r| r.conclusion.symbol == "att" || r.conclusion.symbol == "step"));
+    }
+
+    #[test]
+    fn bounded_why_not_test() {
+        let mut sniffer = Sniffer::from_str(
+            "att(senc(X, k)) => att(senc(senc(X, k), k)). att(senc(a, k)). att(senc(X, Y)) /\\ att(Y) => att(X).",
+        )
+        .unwrap();
+        sniffer.set_bound(Some(Bound::Reject {
+            depth: 4,
+            size: usize::MAX,
+        }));
+        let why_not = sniffer
+            .why_not(&Parser::parse_query("att(b).").unwrap())
+            .unwrap();
+        assert!(why_not.inconclusive);
+        assert!(why_not.to_string().contains("may be derivable"));
+    }
+
+    #[test]
+    fn derivable_why_not_test() {
+        let mut sniffer = Sniffer::from_str("att(a).").unwrap();
+        assert!(sniffer
+            .why_not(&Parser::parse_query("att(a).").unwrap())
+            .is_none());
+    }
+}